@@ -0,0 +1,556 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use clap::{Parser, Subcommand};
+use rbac::{
+    decode_key_part, migrate,
+    schema::{Schema, SchemaError},
+    EntityRelationship, RBACError, ENTITY_TO_ROLE, RBAC, ROLE_TO_ENTITY,
+};
+
+/// Inspect and fix an rbac store.
+///
+/// Relationships are written as `Namespace:id#role@Namespace:id`, i.e. `object#role@subject`,
+/// e.g. `Post:1#Writer@User:1`.
+#[derive(Parser)]
+#[command(name = "rbac-cli")]
+struct Args {
+    /// path to the rocksdb datastore
+    #[arg(long)]
+    db: String,
+
+    /// json schema describing namespaces and roles, required by every command but `dump`
+    #[arg(long)]
+    schema: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// grant relationships, e.g. `add Post:1#Writer@User:1`
+    Add { tuples: Vec<String> },
+    /// revoke relationships
+    Remove { tuples: Vec<String> },
+    /// check a relationship, exits with 1 when denied
+    Check { tuple: String },
+    /// print the path granting a relationship, exits with 1 when denied
+    Explain { tuple: String },
+    /// list objects the subject holds a role on, e.g. `objects User:1 Post#Viewer`
+    Objects { subject: String, role: String },
+    /// list subjects holding a role on an object, e.g. `subjects Post:1#Viewer`
+    Subjects { object_role: String },
+    /// print every vertex and edge as stored
+    Dump,
+    /// write relationships from a file with one tuple per line
+    Import { file: PathBuf },
+    /// write every relationship as tuples, to stdout when no file is given
+    Export { file: Option<PathBuf> },
+    /// bring a store written by an older version up to date
+    Migrate,
+}
+
+#[derive(Debug)]
+enum CliError {
+    // no rocksdb store at the `--db` path
+    NotFound(PathBuf),
+    MissingSchema,
+    Io(io::Error),
+    Schema(SchemaError),
+    Rbac(RBACError),
+    // 1-based line number in an import file
+    Import(usize, SchemaError),
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Io(e)
+    }
+}
+
+impl From<SchemaError> for CliError {
+    fn from(e: SchemaError) -> Self {
+        CliError::Schema(e)
+    }
+}
+
+impl From<RBACError> for CliError {
+    fn from(e: RBACError) -> Self {
+        CliError::Rbac(e)
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match run(args) {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("error: {:?}", e);
+            process::exit(2);
+        }
+    }
+}
+
+fn open(db: &str) -> Result<RBAC, CliError> {
+    // rocksdb would create an empty store at a mistyped path
+    if !Path::new(db).join("CURRENT").is_file() {
+        return Err(CliError::NotFound(PathBuf::from(db)));
+    }
+    Ok(RBAC::open(db)?)
+}
+
+// returns the exit code
+fn run(args: Args) -> Result<i32, CliError> {
+    let rbac = open(&args.db)?;
+
+    if let Command::Dump = args.command {
+        dump(&rbac)?;
+        return Ok(0);
+    }
+
+    let schema = match &args.schema {
+        Some(path) => Schema::load(path)?,
+        None => return Err(CliError::MissingSchema),
+    };
+
+    match args.command {
+        Command::Add { tuples } => {
+            let mut added = 0;
+            for r in parse_all(&schema, &tuples)? {
+                if rbac.add_relationship(&r)? {
+                    added += 1;
+                }
+            }
+            println!("added {}", added);
+        }
+        Command::Remove { tuples } => {
+            let mut removed = 0;
+            for r in parse_all(&schema, &tuples)? {
+                if rbac.remove_relationship(&r)? {
+                    removed += 1;
+                }
+            }
+            println!("removed {}", removed);
+        }
+        Command::Check { tuple } => {
+            let r = parse(&schema, &tuple)?;
+            if !allowed(&rbac, &r)? {
+                println!("denied");
+                return Ok(1);
+            }
+            println!("allowed");
+        }
+        Command::Explain { tuple } => {
+            let r = parse(&schema, &tuple)?;
+            let path = match rbac.explain(&r) {
+                Ok(path) => path,
+                Err(RBACError::VertexNotFound) => None,
+                Err(e) => return Err(e.into()),
+            };
+
+            match path {
+                Some(path) => println!("{}", path.join(" -> ")),
+                None => {
+                    println!("denied");
+                    return Ok(1);
+                }
+            }
+        }
+        Command::Objects { subject, role } => {
+            let subject = schema.parse_entity(&subject)?;
+            let (namespace, role) = role
+                .split_once('#')
+                .ok_or_else(|| SchemaError::InvalidTuple(role.clone()))?;
+            let role = schema.role(namespace, role)?;
+
+            for key in rbac.lookup_objects(&subject, &role)? {
+                println!("{}", display_key(&schema, &key));
+            }
+        }
+        Command::Subjects { object_role } => {
+            let (role, object) = schema.parse_object_role(&object_role)?;
+
+            for key in rbac.lookup_subjects(&role, &object)? {
+                println!("{}", display_key(&schema, &key));
+            }
+        }
+        Command::Import { file } => {
+            let count = import(&rbac, &schema, &fs::read_to_string(file)?)?;
+            println!("imported {}", count);
+        }
+        Command::Export { file } => {
+            let tuples = export(&rbac, &schema)?;
+            match file {
+                Some(file) => fs::write(file, tuples.join("\n") + "\n")?,
+                None => {
+                    for tuple in tuples {
+                        println!("{}", tuple);
+                    }
+                }
+            }
+        }
+        Command::Migrate => {
+            for (name, changed) in migrate::run(&rbac, &schema)? {
+                println!("{}: {} changed", name, changed);
+            }
+        }
+        Command::Dump => unreachable!(),
+    }
+
+    Ok(0)
+}
+
+fn parse(schema: &Schema, tuple: &str) -> Result<EntityRelationship, SchemaError> {
+    let (subject, role, object) = schema.parse_relationship(tuple)?;
+    Ok(EntityRelationship::new(&subject, &role, &object))
+}
+
+// parses everything before anything gets written
+fn parse_all(schema: &Schema, tuples: &[String]) -> Result<Vec<EntityRelationship>, SchemaError> {
+    tuples.iter().map(|tuple| parse(schema, tuple)).collect()
+}
+
+// subjects or objects the store has never seen have no grants
+fn allowed(rbac: &RBAC, r: &EntityRelationship) -> Result<bool, RBACError> {
+    match rbac.allowed(r) {
+        Err(RBACError::VertexNotFound) => Ok(false),
+        result => result,
+    }
+}
+
+fn display_key(schema: &Schema, key: &str) -> String {
+    match schema.entity_from_key(key) {
+        Some(entity) => entity.to_string(),
+        None => key.to_string(),
+    }
+}
+
+fn dump(rbac: &RBAC) -> Result<(), CliError> {
+    let vertices = rbac.get_all_vertices()?;
+    let keys: HashMap<_, _> = vertices.iter().map(|v| (v.id, v.t.to_string())).collect();
+
+    println!("vertices:");
+    for v in &vertices {
+        println!("  {} {}", v.id, v.t.as_str());
+    }
+
+    println!("edges:");
+    for e in rbac.get_all_edges()? {
+        let outbound = keys
+            .get(&e.outbound_id)
+            .cloned()
+            .unwrap_or_else(|| e.outbound_id.to_string());
+        let inbound = keys
+            .get(&e.inbound_id)
+            .cloned()
+            .unwrap_or_else(|| e.inbound_id.to_string());
+
+        println!("  {} -{}-> {}", outbound, e.t.as_str(), inbound);
+    }
+
+    Ok(())
+}
+
+// one tuple per line, blank lines and lines starting with `#` are skipped. returns how many
+// relationships weren't written yet
+fn import(rbac: &RBAC, schema: &Schema, content: &str) -> Result<usize, CliError> {
+    let mut relationships = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        relationships.push(parse(schema, line).map_err(|e| CliError::Import(i + 1, e))?);
+    }
+
+    let mut written = 0;
+    for r in &relationships {
+        if rbac.add_relationship(r)? {
+            written += 1;
+        }
+    }
+
+    Ok(written)
+}
+
+// every `entity_to_role` edge as an `object#role@subject` tuple, sorted
+fn export(rbac: &RBAC, schema: &Schema) -> Result<Vec<String>, CliError> {
+    let keys: HashMap<_, _> = rbac
+        .get_all_vertices()?
+        .into_iter()
+        .map(|v| (v.id, v.t.to_string()))
+        .collect();
+    let edges = rbac.get_all_edges()?;
+
+    // role vertex -> the object it's a role on
+    let objects: HashMap<_, _> = edges
+        .iter()
+        .filter(|e| e.t.as_str() == ROLE_TO_ENTITY)
+        .map(|e| (e.outbound_id, e.inbound_id))
+        .collect();
+
+    let mut tuples = Vec::new();
+    for edge in edges.iter().filter(|e| e.t.as_str() == ENTITY_TO_ROLE) {
+        let subject_key = keys.get(&edge.outbound_id);
+        let role_key = keys.get(&edge.inbound_id);
+        let object_key = objects.get(&edge.inbound_id).and_then(|id| keys.get(id));
+
+        let tuple = match (subject_key, role_key, object_key) {
+            (Some(subject_key), Some(role_key), Some(object_key)) => {
+                // role vertices are keyed `{object key}_{Role}`
+                let role = role_key
+                    .strip_prefix(object_key.as_str())
+                    .and_then(|role| role.strip_prefix('_'))
+                    .and_then(decode_key_part);
+
+                match (
+                    schema.entity_from_key(subject_key),
+                    role,
+                    schema.entity_from_key(object_key),
+                ) {
+                    (Some(subject), Some(role), Some(object)) => {
+                        Some(format!("{}#{}@{}", object, role, subject))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        match tuple {
+            Some(tuple) => tuples.push(tuple),
+            None => eprintln!(
+                "skipping edge {} -> {} that doesn't fit the schema",
+                edge.outbound_id, edge.inbound_id
+            ),
+        }
+    }
+
+    tuples.sort();
+    Ok(tuples)
+}
+
+#[cfg(test)]
+mod tests {
+    use rbac::{INHERITS, MEMBERSHIP};
+
+    use super::*;
+
+    const SCHEMA: &str = r#"{
+        "namespaces": [
+            { "name": "User" },
+            { "name": "Post", "roles": [
+                { "name": "Writer" },
+                { "name": "Viewer", "child_of": "Writer" }
+            ] },
+            { "name": "Group", "roles": [
+                { "name": "Admin" },
+                { "name": "Member", "child_of": "Admin", "membership": true }
+            ] }
+        ]
+    }"#;
+
+    #[test]
+    fn cli_import_export() {
+        let rbac = RBAC::new("./rocksdb/test1");
+        rbac.clear(true).unwrap();
+        let schema = Schema::from_json(SCHEMA).unwrap();
+
+        let content = "
+            # alice writes, the foo group views
+            Post:1#Writer@User:1
+            Group:1#Member@User:2
+
+            Post:1#Viewer@Group:1
+        ";
+        assert_eq!(import(&rbac, &schema, content).unwrap(), 3);
+        // already written
+        assert_eq!(import(&rbac, &schema, content).unwrap(), 0);
+
+        let r = parse(&schema, "Post:1#Viewer@User:2").unwrap();
+        assert_eq!(allowed(&rbac, &r).unwrap(), true);
+
+        let r = parse(&schema, "Post:1#Writer@User:3").unwrap();
+        assert_eq!(allowed(&rbac, &r).unwrap(), false);
+
+        // only the written relationships, not the materialized hierarchy
+        assert_eq!(
+            export(&rbac, &schema).unwrap(),
+            vec![
+                "Group:1#Member@User:2",
+                "Post:1#Viewer@Group:1",
+                "Post:1#Writer@User:1",
+            ]
+        );
+
+        // nothing is written when a line doesn't parse
+        let result = import(&rbac, &schema, "Post:2#Writer@User:1\nPost:2#Member@User:1");
+        assert!(matches!(
+            result,
+            Err(CliError::Import(2, SchemaError::UnknownRole(_, _)))
+        ));
+        assert_eq!(export(&rbac, &schema).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn cli_open_missing_store() {
+        let path = "./rocksdb/cli-missing";
+        assert!(matches!(open(path), Err(CliError::NotFound(_))));
+        // nothing was created at the mistyped path
+        assert!(!Path::new(path).exists());
+
+        drop(RBAC::new("./rocksdb/test2"));
+        assert!(open("./rocksdb/test2").is_ok());
+    }
+
+    // a store as written before keys were escaped, keyed by the raw ids and mostly under
+    // random vertex ids. `canonical` vertices were written again by a newer version.
+    fn legacy_store(path: &str) {
+        RBAC::new(path).clear(true).unwrap();
+        let db = indradb::RocksdbDatastore::new_db(path).unwrap();
+
+        let mut ids = HashMap::new();
+        let vertices = [
+            ("User_alice", false),
+            ("User_bob", false),
+            ("User_carol_1", false),
+            ("User_dave", true),
+            ("Group_1", false),
+            ("Group_1_Admin", false),
+            // never got a role_to_entity edge
+            ("Group_1_Member", false),
+            ("Group_2", false),
+            ("Group_2_Member", false),
+            ("Post_1", true),
+            ("Post_1_Writer", true),
+            ("Post_1_Viewer", true),
+            ("Post_my_post", false),
+            ("Post_my_post_Writer", false),
+            ("Post_my_post_Viewer", false),
+        ];
+        for (key, canonical) in vertices {
+            let t = indradb::Identifier::new(key).unwrap();
+            let v = if canonical {
+                indradb::Vertex::with_id(rbac::vertex_id(key), t)
+            } else {
+                indradb::Vertex::new(t)
+            };
+            db.create_vertex(&v).unwrap();
+            ids.insert(key, v.id);
+        }
+
+        let edges = [
+            ("Group_1_Admin", ROLE_TO_ENTITY, "Group_1"),
+            ("Group_1_Admin", INHERITS, "Group_1_Member"),
+            ("Group_2_Member", ROLE_TO_ENTITY, "Group_2"),
+            ("Post_1_Writer", ROLE_TO_ENTITY, "Post_1"),
+            ("Post_1_Viewer", ROLE_TO_ENTITY, "Post_1"),
+            ("Post_1_Writer", INHERITS, "Post_1_Viewer"),
+            ("Post_my_post_Writer", ROLE_TO_ENTITY, "Post_my_post"),
+            ("Post_my_post_Viewer", ROLE_TO_ENTITY, "Post_my_post"),
+            ("Post_my_post_Writer", INHERITS, "Post_my_post_Viewer"),
+            ("User_alice", ENTITY_TO_ROLE, "Group_1_Member"),
+            ("User_carol_1", ENTITY_TO_ROLE, "Group_1_Admin"),
+            ("User_bob", ENTITY_TO_ROLE, "Group_2_Member"),
+            ("Group_2", ENTITY_TO_ROLE, "Post_my_post_Viewer"),
+            ("User_carol_1", ENTITY_TO_ROLE, "Post_my_post_Writer"),
+            ("User_dave", ENTITY_TO_ROLE, "Post_1_Writer"),
+        ];
+        for (outbound, t, inbound) in edges {
+            let t = indradb::Identifier::new(t).unwrap();
+            db.create_edge(&indradb::Edge::new(ids[outbound], t, ids[inbound]))
+                .unwrap();
+        }
+
+        db.set_properties(
+            indradb::SpecificVertexQuery::single(ids["Group_2_Member"]),
+            indradb::Identifier::new(MEMBERSHIP).unwrap(),
+            &indradb::Json::new(serde_json::Value::Bool(true)),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn cli_migrate_legacy_store() {
+        legacy_store("./rocksdb/test3");
+        let rbac = RBAC::new("./rocksdb/test3");
+        let schema = Schema::from_json(SCHEMA).unwrap();
+
+        let checks = [
+            ("Group:1#Member@User:alice", true),
+            ("Group:1#Member@User:carol_1", true),
+            ("Group:1#Admin@User:alice", false),
+            ("Post:my_post#Viewer@User:bob", true),
+            ("Post:my_post#Writer@User:bob", false),
+            ("Post:my_post#Viewer@User:carol_1", true),
+            ("Post:1#Viewer@User:dave", true),
+            ("Post:1#Writer@User:dave", true),
+            ("Post:1#Writer@User:alice", false),
+        ];
+
+        // checks on vertices under random ids can't be answered before
+        let before: Vec<_> = checks
+            .iter()
+            .map(|(tuple, _)| rbac.allowed(&parse(&schema, tuple).unwrap()).ok())
+            .collect();
+        assert_eq!(before.iter().filter(|answer| answer.is_some()).count(), 2);
+
+        let changed = migrate::run(&rbac, &schema).unwrap();
+        assert_eq!(
+            changed,
+            vec![("canonical_keys", 11), ("backfill_membership", 0)]
+        );
+
+        for ((tuple, expected), before) in checks.iter().zip(before) {
+            let answer = rbac.allowed(&parse(&schema, tuple).unwrap()).unwrap();
+            assert_eq!(answer, *expected, "{}", tuple);
+            if let Some(before) = before {
+                assert_eq!(answer, before, "{}", tuple);
+            }
+        }
+
+        // the role without a role_to_entity edge is still a role, not an entity
+        let keys: Vec<String> = rbac
+            .get_all_vertices()
+            .unwrap()
+            .iter()
+            .map(|v| v.t.to_string())
+            .collect();
+        assert!(keys.iter().any(|key| key == "Group_1_Member"));
+        assert!(keys.iter().any(|key| key == "User_carol-5F1"));
+        assert!(!keys.iter().any(|key| key == "Group_1-5FMember"));
+        for v in rbac.get_all_vertices().unwrap() {
+            assert_eq!(v.id, rbac::vertex_id(v.t.as_str()));
+        }
+
+        // a migrated store has nothing left to change
+        let changed = migrate::run(&rbac, &schema).unwrap();
+        assert_eq!(
+            changed,
+            vec![("canonical_keys", 0), ("backfill_membership", 0)]
+        );
+    }
+
+    #[test]
+    fn cli_parse_tuples() {
+        let schema = Schema::from_json(SCHEMA).unwrap();
+
+        let (subject, role, object) = schema.parse_relationship("Post:1#Writer@User:1").unwrap();
+        assert_eq!(subject.to_string(), "User:1");
+        assert_eq!(role.name(), "Writer");
+        assert_eq!(object.to_string(), "Post:1");
+
+        assert!(schema.parse_relationship("Post:1#Writer").is_err());
+        assert!(schema.parse_relationship("Post#Writer@User:1").is_err());
+        assert!(schema.parse_relationship("Page:1#Writer@User:1").is_err());
+
+        assert_eq!(display_key(&schema, "Group_1"), "Group:1");
+    }
+}
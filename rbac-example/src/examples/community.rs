@@ -11,6 +11,8 @@ pub enum Namespaces {
     Post(Option<String>),
     #[roles(GroupRoles)]
     Group(Option<String>),
+    #[roles(ForumRoles)]
+    Forum(Option<String>),
 }
 
 // roles
@@ -20,6 +22,7 @@ pub enum Namespaces {
 pub enum GroupRoles {
     Admin,
     #[child_of(Admin)]
+    #[membership]
     #[default]
     Member,
 }
@@ -27,10 +30,28 @@ pub enum GroupRoles {
 #[derive(Role, Default)]
 #[namespace(Namespaces::Post)]
 pub enum PostRoles {
+    // admins of the owning group write the post
+    #[implied_by(GroupRoles::Admin, via = OwnedBy)]
     Writer,
     #[child_of(Writer)]
     #[default]
     Viewer,
+    // links the post to its group, grants nothing by itself
+    OwnedBy,
+}
+
+// commenting is implied by both moderating and writing
+#[derive(Role, Default)]
+#[namespace(Namespaces::Forum)]
+pub enum ForumRoles {
+    Owner,
+    #[child_of(Owner)]
+    Moderator,
+    #[child_of(Owner)]
+    Writer,
+    #[child_of(Moderator, Writer)]
+    #[default]
+    Commenter,
 }
 
 // entities
@@ -59,3 +80,11 @@ pub struct Group {
     pub id: i32,
     pub name: &'static str,
 }
+
+#[allow(dead_code)]
+#[derive(ToNode)]
+#[namespace(Namespaces::Forum)]
+pub struct Forum {
+    pub id: i32,
+    pub name: &'static str,
+}
@@ -0,0 +1,80 @@
+use axum::{
+    extract::Path,
+    http::request::Parts,
+    routing::{get, put},
+    Router,
+};
+use rbac::{middleware::RequireRoleLayer, nonblocking::AsyncRBAC};
+
+use super::community::{Group, GroupRoles, Post, PostRoles, User};
+
+// authentication is out of scope here, the caller just claims to be a user
+fn user_from_header(parts: &Parts) -> Option<User> {
+    let id = parts
+        .headers
+        .get("x-user-id")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    Some(User { id, nickname: "" })
+}
+
+fn post_from_path(params: &std::collections::HashMap<String, String>) -> Option<Post> {
+    let id = params.get("id")?.parse().ok()?;
+    Some(Post {
+        id,
+        author_id: 0,
+        title: "",
+    })
+}
+
+fn group_from_path(params: &std::collections::HashMap<String, String>) -> Option<Group> {
+    let id = params.get("id")?.parse().ok()?;
+    Some(Group { id, name: "" })
+}
+
+async fn show_post(Path(id): Path<i32>) -> String {
+    format!("post {}", id)
+}
+
+async fn edit_post(Path(id): Path<i32>) -> String {
+    format!("post {} updated", id)
+}
+
+async fn show_group(Path(id): Path<i32>) -> String {
+    format!("group {}", id)
+}
+
+// every route declares the role it needs on the object named by its path
+#[allow(dead_code)]
+pub fn app(rbac: AsyncRBAC) -> Router {
+    let view_posts = Router::new()
+        .route("/posts/:id", get(show_post))
+        .route_layer(RequireRoleLayer::new(
+            rbac.clone(),
+            user_from_header,
+            PostRoles::Viewer,
+            post_from_path,
+        ));
+
+    let edit_posts = Router::new()
+        .route("/posts/:id", put(edit_post))
+        .route_layer(RequireRoleLayer::new(
+            rbac.clone(),
+            user_from_header,
+            PostRoles::Writer,
+            post_from_path,
+        ));
+
+    let groups = Router::new()
+        .route("/groups/:id", get(show_group))
+        .route_layer(RequireRoleLayer::new(
+            rbac,
+            user_from_header,
+            GroupRoles::Member,
+            group_from_path,
+        ));
+
+    view_posts.merge(edit_posts).merge(groups)
+}
@@ -7,7 +7,10 @@ fn main() {
 #[cfg(test)]
 mod tests {
     mod community {
-        use rbac::EntityRelationship;
+        use rbac::{
+            EntityRelationship, NamespaceToString, Node, Precondition, RBACError, ToNode, Update,
+            WriteMode,
+        };
 
         use crate::examples::community::*;
 
@@ -40,6 +43,8 @@ mod tests {
 
         const GROUP_FOO: Group = Group { id: 1, name: "Foo" };
 
+        const GROUP_BAR: Group = Group { id: 2, name: "Bar" };
+
         const POST_BY_FOO: Post = Post {
             id: 3,
             author_id: GROUP_FOO.id,
@@ -109,5 +114,870 @@ mod tests {
 
             assert_eq!(result.unwrap(), true);
         }
+
+        #[test]
+        fn community_depth_limit() {
+            // charlie -> Group_1_Member -> Group_1 -> Post_3_Writer -> Post_3_Viewer -> Post_3
+            let server = rbac::RBAC::new("./rocksdb/test3").with_max_depth(3);
+            server.clear(true).unwrap();
+
+            let r = EntityRelationship::new(&USER_CHARLIE, &GroupRoles::Member, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&GROUP_FOO, &PostRoles::Writer, &POST_BY_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            // the grant is 4 vertices away, so the check is cut off instead of denied
+            let test = EntityRelationship::new(&USER_CHARLIE, &PostRoles::Viewer, &POST_BY_FOO);
+            let result = server.allowed(&test);
+            assert!(matches!(result, Err(RBACError::DepthLimitExceeded(3))));
+
+            // within the limit the answer is still a plain denial
+            let test = EntityRelationship::new(&UESR_ALICE, &PostRoles::Viewer, &POST_BY_FOO);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+
+            let server = server.with_max_depth(4);
+            let test = EntityRelationship::new(&USER_CHARLIE, &PostRoles::Viewer, &POST_BY_FOO);
+            assert_eq!(server.allowed(&test).unwrap(), true);
+        }
+
+        #[test]
+        fn community_cyclic_groups() {
+            let server = rbac::RBAC::new("./rocksdb/test4");
+            server.clear(true).unwrap();
+
+            // foo and bar are members of each other
+            let r = EntityRelationship::new(&GROUP_FOO, &GroupRoles::Member, &GROUP_BAR);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&GROUP_BAR, &GroupRoles::Member, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &GroupRoles::Member, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&USER_CHARLIE, &PostRoles::Writer, &POST_BY_CHARLIE);
+            server.add_relationship(&r).unwrap();
+
+            // alice is a member of bar through foo
+            let test = EntityRelationship::new(&UESR_ALICE, &GroupRoles::Member, &GROUP_BAR);
+            assert_eq!(server.allowed(&test).unwrap(), true);
+
+            // the cycle doesn't fail a denial, it's reported alongside it
+            let test = EntityRelationship::new(&UESR_ALICE, &PostRoles::Viewer, &POST_BY_CHARLIE);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+
+            let diagnosis = server.diagnose(&test).unwrap();
+            assert_eq!(diagnosis.path, None);
+            let cycle = diagnosis.cycle.unwrap();
+            assert_eq!(cycle.first(), cycle.last());
+            assert!(cycle.contains(&"Group_1".to_string()));
+            assert!(cycle.contains(&"Group_2".to_string()));
+        }
+
+        #[test]
+        fn community_cyclic_groups_cross_edge() {
+            let server = rbac::RBAC::new("./rocksdb/test17");
+            server.clear(true).unwrap();
+
+            // alice reaches both groups directly, so on a breadth-first walk neither closes
+            // the cycle back onto an ancestor
+            let r = EntityRelationship::new(&GROUP_FOO, &GroupRoles::Member, &GROUP_BAR);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&GROUP_BAR, &GroupRoles::Member, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &GroupRoles::Member, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &GroupRoles::Member, &GROUP_BAR);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&USER_CHARLIE, &PostRoles::Writer, &POST_BY_CHARLIE);
+            server.add_relationship(&r).unwrap();
+
+            let test = EntityRelationship::new(&UESR_ALICE, &PostRoles::Viewer, &POST_BY_CHARLIE);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+
+            let cycle = server.diagnose(&test).unwrap().cycle.unwrap();
+            assert_eq!(cycle.first(), cycle.last());
+            assert!(cycle.contains(&"Group_1".to_string()));
+            assert!(cycle.contains(&"Group_2".to_string()));
+
+            // a granted check has nothing to report
+            let test = EntityRelationship::new(&UESR_ALICE, &GroupRoles::Member, &GROUP_BAR);
+            let diagnosis = server.diagnose(&test).unwrap();
+            assert!(diagnosis.path.is_some());
+            assert_eq!(diagnosis.cycle, None);
+        }
+
+        #[test]
+        fn community_no_grants_through_objects() {
+            let server = rbac::RBAC::new("./rocksdb/test5");
+            server.clear(true).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&USER_CHARLIE, &PostRoles::Writer, &POST_BY_CHARLIE);
+            server.add_relationship(&r).unwrap();
+
+            // alice's post is granted a role on charlie's post
+            let r = EntityRelationship::new(&POST_BY_ALICE, &PostRoles::Writer, &POST_BY_CHARLIE);
+            server.add_relationship(&r).unwrap();
+
+            // post roles are not memberships, so alice doesn't act as her post
+            let test = EntityRelationship::new(&UESR_ALICE, &PostRoles::Viewer, &POST_BY_CHARLIE);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+
+            let test =
+                EntityRelationship::new(&POST_BY_ALICE, &PostRoles::Viewer, &POST_BY_CHARLIE);
+            assert_eq!(server.allowed(&test).unwrap(), true);
+
+            // inheritance only flows from parent to child
+            let test = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            assert_eq!(server.allowed(&test).unwrap(), true);
+
+            let r = EntityRelationship::new(&USER_BOB, &PostRoles::Viewer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            let test = EntityRelationship::new(&USER_BOB, &PostRoles::Writer, &POST_BY_ALICE);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+        }
+
+        #[test]
+        fn community_group_membership() {
+            let server = rbac::RBAC::new("./rocksdb/test6");
+            server.clear(true).unwrap();
+
+            // bob administrates foo, charlie is a member of bar
+            let r = EntityRelationship::new(&USER_BOB, &GroupRoles::Admin, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&USER_CHARLIE, &GroupRoles::Member, &GROUP_BAR);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&GROUP_FOO, &PostRoles::Writer, &POST_BY_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            // admin implies member, and members act as the group
+            let test = EntityRelationship::new(&USER_BOB, &PostRoles::Writer, &POST_BY_FOO);
+            assert_eq!(server.allowed(&test).unwrap(), true);
+
+            // bar has nothing to do with foo's post
+            let test = EntityRelationship::new(&USER_CHARLIE, &PostRoles::Viewer, &POST_BY_FOO);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+
+            // foo's grant doesn't leak onto other posts
+            let test = EntityRelationship::new(&USER_BOB, &PostRoles::Viewer, &POST_BY_ALICE);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+
+            // membership doesn't flow back up to admin
+            let r = EntityRelationship::new(&UESR_ALICE, &GroupRoles::Member, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let test = EntityRelationship::new(&UESR_ALICE, &GroupRoles::Admin, &GROUP_FOO);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+        }
+
+        #[test]
+        fn community_group_written_as_subject_first() {
+            let server = rbac::RBAC::new("./rocksdb/test18");
+            server.clear(true).unwrap();
+
+            // foo views the post before it has any members, so its vertex is first written as
+            // a subject
+            let r = EntityRelationship::new(&GROUP_FOO, &PostRoles::Viewer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&USER_BOB, &GroupRoles::Member, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+            let r = EntityRelationship::new(&USER_CHARLIE, &GroupRoles::Admin, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let test = EntityRelationship::new(&USER_BOB, &PostRoles::Viewer, &POST_BY_ALICE);
+            assert_eq!(server.allowed(&test).unwrap(), true);
+            // admins are members too
+            let test = EntityRelationship::new(&USER_CHARLIE, &PostRoles::Viewer, &POST_BY_ALICE);
+            assert_eq!(server.allowed(&test).unwrap(), true);
+            let test = EntityRelationship::new(&USER_BOB, &PostRoles::Writer, &POST_BY_ALICE);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+
+            let subjects = server
+                .lookup_subjects(&PostRoles::Viewer, &POST_BY_ALICE)
+                .unwrap();
+            assert_eq!(subjects, vec!["Group_1", "User_2", "User_3"]);
+        }
+
+        #[test]
+        fn community_lookup() {
+            let server = rbac::RBAC::new("./rocksdb/test7");
+            server.clear(true).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &GroupRoles::Member, &GROUP_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&GROUP_FOO, &PostRoles::Viewer, &POST_BY_FOO);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&USER_BOB, &PostRoles::Viewer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            let objects = server
+                .lookup_objects(&UESR_ALICE, &PostRoles::Viewer)
+                .unwrap();
+            assert_eq!(objects, vec!["Post_1", "Post_3"]);
+
+            let objects = server
+                .lookup_objects(&UESR_ALICE, &PostRoles::Writer)
+                .unwrap();
+            assert_eq!(objects, vec!["Post_1"]);
+
+            let subjects = server
+                .lookup_subjects(&PostRoles::Viewer, &POST_BY_ALICE)
+                .unwrap();
+            assert_eq!(subjects, vec!["User_1", "User_2"]);
+
+            // the group and its members
+            let subjects = server
+                .lookup_subjects(&PostRoles::Viewer, &POST_BY_FOO)
+                .unwrap();
+            assert_eq!(subjects, vec!["Group_1", "User_1"]);
+        }
+
+        #[test]
+        fn community_remove_relationship() {
+            let server = rbac::RBAC::new("./rocksdb/test8");
+            server.clear(true).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            let r = EntityRelationship::new(&USER_BOB, &PostRoles::Viewer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+            assert_eq!(server.allowed(&r).unwrap(), true);
+
+            assert_eq!(server.remove_relationship(&r).unwrap(), true);
+            assert_eq!(server.allowed(&r).unwrap(), false);
+
+            // nothing left to remove
+            assert_eq!(server.remove_relationship(&r).unwrap(), false);
+
+            let r = EntityRelationship::new(&USER_CHARLIE, &PostRoles::Viewer, &POST_BY_ALICE);
+            assert_eq!(server.remove_relationship(&r).unwrap(), false);
+
+            // alice is untouched
+            let test = EntityRelationship::new(&UESR_ALICE, &PostRoles::Viewer, &POST_BY_ALICE);
+            assert_eq!(server.allowed(&test).unwrap(), true);
+        }
+
+        #[test]
+        fn community_diamond_roles() {
+            let server = rbac::RBAC::new("./rocksdb/test12");
+            server.clear(true).unwrap();
+
+            let forum = Forum {
+                id: 1,
+                name: "General",
+            };
+
+            ForumRoles::Owner
+                .grant(&server, &UESR_ALICE, &forum)
+                .unwrap();
+            ForumRoles::Moderator
+                .grant(&server, &USER_BOB, &forum)
+                .unwrap();
+            ForumRoles::Writer
+                .grant(&server, &USER_CHARLIE, &forum)
+                .unwrap();
+
+            // the owner reaches the commenter role through both parents
+            let path = server
+                .explain(&EntityRelationship::new(
+                    &UESR_ALICE,
+                    &ForumRoles::Commenter,
+                    &forum,
+                ))
+                .unwrap()
+                .unwrap();
+            assert_eq!(path.first().unwrap(), "User_1");
+            assert_eq!(path.last().unwrap(), "Forum_1");
+
+            for user in [&USER_BOB, &USER_CHARLIE] {
+                assert_eq!(
+                    ForumRoles::Commenter.check(&server, user, &forum).unwrap(),
+                    true
+                );
+            }
+
+            // converging paths are not cycles
+            assert_eq!(
+                ForumRoles::Writer
+                    .check(&server, &USER_BOB, &forum)
+                    .unwrap(),
+                false
+            );
+            assert_eq!(
+                ForumRoles::Moderator
+                    .check(&server, &USER_CHARLIE, &forum)
+                    .unwrap(),
+                false
+            );
+            assert_eq!(
+                ForumRoles::Owner.check(&server, &USER_BOB, &forum).unwrap(),
+                false
+            );
+        }
+
+        #[test]
+        fn community_implied_roles() {
+            let server = rbac::RBAC::new("./rocksdb/test13");
+            server.clear(true).unwrap();
+
+            // alice administers bar, bob is a member, and foo as a whole is an admin of bar
+            GroupRoles::Admin
+                .grant(&server, &UESR_ALICE, &GROUP_BAR)
+                .unwrap();
+            GroupRoles::Member
+                .grant(&server, &USER_BOB, &GROUP_BAR)
+                .unwrap();
+            GroupRoles::Member
+                .grant(&server, &USER_CHARLIE, &GROUP_FOO)
+                .unwrap();
+            GroupRoles::Admin
+                .grant(&server, &GROUP_FOO, &GROUP_BAR)
+                .unwrap();
+
+            // one write per post, none per admin
+            PostRoles::OwnedBy
+                .grant(&server, &GROUP_BAR, &POST_BY_FOO)
+                .unwrap();
+
+            assert_eq!(
+                PostRoles::Writer
+                    .check(&server, &UESR_ALICE, &POST_BY_FOO)
+                    .unwrap(),
+                true
+            );
+            // writers are viewers as well
+            assert_eq!(
+                PostRoles::Viewer
+                    .check(&server, &UESR_ALICE, &POST_BY_FOO)
+                    .unwrap(),
+                true
+            );
+            assert_eq!(
+                PostRoles::Writer
+                    .check(&server, &USER_BOB, &POST_BY_FOO)
+                    .unwrap(),
+                false
+            );
+            // admin of bar through the membership of foo
+            assert_eq!(
+                PostRoles::Writer
+                    .check(&server, &USER_CHARLIE, &POST_BY_FOO)
+                    .unwrap(),
+                true
+            );
+
+            let test = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_FOO);
+            assert_eq!(
+                server.explain(&test).unwrap().unwrap(),
+                vec![
+                    "User_1",
+                    "Group_2_Admin",
+                    "Group_2",
+                    "Post_3_OwnedBy",
+                    "Post_3"
+                ]
+            );
+
+            // giving the post away revokes it
+            let r = EntityRelationship::new(&GROUP_BAR, &PostRoles::OwnedBy, &POST_BY_FOO);
+            assert_eq!(server.remove_relationship(&r).unwrap(), true);
+            assert_eq!(server.allowed(&test).unwrap(), false);
+        }
+
+        #[test]
+        fn community_implied_lookups() {
+            let server = rbac::RBAC::new("./rocksdb/test21");
+            server.clear(true).unwrap();
+
+            let group_baz = Group { id: 3, name: "Baz" };
+            let post_by_baz = Post {
+                id: 4,
+                author_id: group_baz.id,
+                title: "Hello, World!",
+            };
+
+            // alice administers bar, bob is a member, and foo as a whole is an admin of bar
+            GroupRoles::Admin
+                .grant(&server, &UESR_ALICE, &GROUP_BAR)
+                .unwrap();
+            GroupRoles::Member
+                .grant(&server, &USER_BOB, &GROUP_BAR)
+                .unwrap();
+            GroupRoles::Member
+                .grant(&server, &USER_CHARLIE, &GROUP_FOO)
+                .unwrap();
+            GroupRoles::Admin
+                .grant(&server, &GROUP_FOO, &GROUP_BAR)
+                .unwrap();
+            PostRoles::Writer
+                .grant(&server, &UESR_ALICE, &POST_BY_ALICE)
+                .unwrap();
+
+            // bar owns one post directly and one as a member of baz
+            PostRoles::OwnedBy
+                .grant(&server, &GROUP_BAR, &POST_BY_FOO)
+                .unwrap();
+            GroupRoles::Member
+                .grant(&server, &GROUP_BAR, &group_baz)
+                .unwrap();
+            PostRoles::OwnedBy
+                .grant(&server, &group_baz, &post_by_baz)
+                .unwrap();
+
+            assert_eq!(
+                PostRoles::Writer
+                    .check(&server, &UESR_ALICE, &post_by_baz)
+                    .unwrap(),
+                true
+            );
+
+            let objects = server
+                .lookup_objects(&UESR_ALICE, &PostRoles::Viewer)
+                .unwrap();
+            assert_eq!(objects, vec!["Post_1", "Post_3", "Post_4"]);
+
+            let objects = server
+                .lookup_objects(&USER_CHARLIE, &PostRoles::Writer)
+                .unwrap();
+            assert_eq!(objects, vec!["Post_3", "Post_4"]);
+
+            let subjects = server
+                .lookup_subjects(&PostRoles::Writer, &POST_BY_FOO)
+                .unwrap();
+            assert_eq!(subjects, vec!["Group_1", "User_1", "User_3"]);
+
+            // lookups answer the same as checks
+            let subjects: Vec<&dyn ToNode> = vec![
+                &UESR_ALICE,
+                &USER_BOB,
+                &USER_CHARLIE,
+                &GROUP_FOO,
+                &GROUP_BAR,
+                &group_baz,
+            ];
+            let posts: Vec<&dyn ToNode> = vec![&POST_BY_ALICE, &POST_BY_FOO, &post_by_baz];
+            for role in [PostRoles::Writer, PostRoles::Viewer] {
+                for subject in &subjects {
+                    let subject_key = subject.to_node(None).to_string();
+                    let objects = server.lookup_objects(*subject, &role).unwrap();
+
+                    for post in &posts {
+                        let post_key = post.to_node(None).to_string();
+                        let r = EntityRelationship::new(*subject, &role, *post);
+                        let allowed = server.allowed(&r).unwrap();
+
+                        assert_eq!(objects.contains(&post_key), allowed, "{}", post_key);
+                        let holders = server.lookup_subjects(&role, *post).unwrap();
+                        assert_eq!(holders.contains(&subject_key), allowed, "{}", subject_key);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn community_parse_keys() {
+            let server = rbac::RBAC::new("./rocksdb/test14");
+            server.clear(true).unwrap();
+
+            let r = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            // lookups return stored keys, typed again by the derived parsers
+            let objects: Vec<Namespaces> = server
+                .lookup_objects(&UESR_ALICE, &PostRoles::Viewer)
+                .unwrap()
+                .iter()
+                .map(|key| key.parse().unwrap())
+                .collect();
+            assert!(matches!(&objects[..], [Namespaces::Post(Some(id))] if id == "1"));
+
+            // every stored key parses back into the node it was made from
+            for v in server.get_all_vertices().unwrap() {
+                let node = Node::parse::<Namespaces>(v.t.as_str()).unwrap();
+                assert_eq!(node.to_string(), v.t.as_str());
+            }
+
+            let node = Node::parse::<Namespaces>("Post_1_Viewer").unwrap();
+            assert_eq!(node.namespace().to_string(), "Post_1");
+            assert_eq!(node.id(), "Viewer");
+
+            // ids may contain underscores, escaped in keys
+            let node = Node::parse::<Namespaces>("User_alice-5F1").unwrap();
+            assert_eq!(node.namespace().to_string(), "User");
+            assert_eq!(node.id(), "alice_1");
+            assert!(matches!(
+                Namespaces::try_from("User_alice-5F1"),
+                Ok(Namespaces::User(Some(id))) if id == "alice_1"
+            ));
+            assert!(Node::parse::<Namespaces>("User_alice_1").is_err());
+
+            assert!(matches!("Viewer".parse::<PostRoles>(), Ok(PostRoles::Viewer)));
+            assert!(GroupRoles::try_from("Viewer").is_err());
+            assert!(Node::parse::<Namespaces>("Page_1").is_err());
+            assert!(Node::parse::<Namespaces>("Post_").is_err());
+        }
+
+        #[test]
+        fn community_key_encoding() {
+            let server = rbac::RBAC::new("./rocksdb/test15");
+            server.clear(true).unwrap();
+
+            // an entity id can't pass for a role on another object
+            let entity = Node::new(Box::new(Namespaces::Post(None)), "1_Writer".to_string());
+            let role = PostRoles::Writer.to_node(Some("1".to_string()));
+            assert_eq!(entity.to_string(), "Post_1-5FWriter");
+            assert_eq!(role.to_string(), "Post_1_Writer");
+
+            let user = Node::new(Box::new(Namespaces::User(None)), "alice".to_string());
+            let r = EntityRelationship::new_from_node(user, role, entity);
+            server.add_relationship(&r).unwrap();
+
+            // both are stored as separate vertices
+            let keys: Vec<String> = server
+                .get_all_vertices()
+                .unwrap()
+                .iter()
+                .map(|v| v.t.to_string())
+                .collect();
+            assert!(keys.iter().any(|key| key == "Post_1-5FWriter"));
+            assert!(keys.iter().any(|key| key == "Post_1_Writer"));
+
+            // long ids are hashed into valid identifiers and can't be parsed back
+            let long = Node::new(Box::new(Namespaces::Post(None)), "x".repeat(1000));
+            assert!(long.to_string().len() <= 2 * rbac::MAX_KEY_PART + 1);
+            assert_eq!(long.to_identifier().as_str(), long.to_string());
+            assert!(Node::parse::<Namespaces>(&long.to_string()).is_err());
+
+            // vertex ids follow from the keys
+            for v in server.get_all_vertices().unwrap() {
+                assert_eq!(v.id, rbac::vertex_id(v.t.as_str()));
+            }
+        }
+
+        #[test]
+        fn community_write_modes() {
+            let server = rbac::RBAC::new("./rocksdb/test16");
+            server.clear(true).unwrap();
+
+            let writer = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            assert_eq!(server.add_relationship(&writer).unwrap(), true);
+            // writing it again is a no-op
+            assert_eq!(server.add_relationship(&writer).unwrap(), false);
+            assert!(matches!(
+                server.write_relationship(&writer, WriteMode::Create, &[]),
+                Err(RBACError::RelationshipExists)
+            ));
+
+            // preconditions look at written relationships only, alice is a viewer by inheritance
+            let viewer = EntityRelationship::new(&USER_BOB, &PostRoles::Viewer, &POST_BY_ALICE);
+            let preconditions = [
+                Precondition::MustExist(EntityRelationship::new(
+                    &UESR_ALICE,
+                    &PostRoles::Writer,
+                    &POST_BY_ALICE,
+                )),
+                Precondition::MustExist(EntityRelationship::new(
+                    &UESR_ALICE,
+                    &PostRoles::Viewer,
+                    &POST_BY_ALICE,
+                )),
+            ];
+            assert!(matches!(
+                server.write_relationship(&viewer, WriteMode::Create, &preconditions),
+                Err(RBACError::PreconditionFailed(1))
+            ));
+            assert_eq!(server.has_relationship(&viewer).unwrap(), false);
+
+            let preconditions = [Precondition::MustNotExist(EntityRelationship::new(
+                &USER_BOB,
+                &PostRoles::Writer,
+                &POST_BY_ALICE,
+            ))];
+            assert_eq!(
+                server
+                    .write_relationship(&viewer, WriteMode::Create, &preconditions)
+                    .unwrap(),
+                true
+            );
+            assert_eq!(server.has_relationship(&viewer).unwrap(), true);
+
+            assert_eq!(server.remove_relationship(&viewer).unwrap(), true);
+            assert_eq!(server.remove_relationship(&viewer).unwrap(), false);
+        }
+
+        #[test]
+        fn community_write_batch() {
+            let server = rbac::RBAC::new("./rocksdb/test20");
+            server.clear(true).unwrap();
+
+            let writer =
+                || EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            let viewer = || EntityRelationship::new(&USER_BOB, &PostRoles::Viewer, &POST_BY_ALICE);
+            let member = || EntityRelationship::new(&USER_CHARLIE, &GroupRoles::Member, &GROUP_FOO);
+
+            let changed = server
+                .write_batch(&[Update::Touch(writer()), Update::Touch(viewer())])
+                .unwrap();
+            assert_eq!(changed, vec![true, true]);
+
+            // creating the writer again fails the batch, the updates before it are undone
+            let result = server.write_batch(&[
+                Update::Touch(member()),
+                Update::Delete(viewer()),
+                Update::Create(writer()),
+            ]);
+            assert!(matches!(result, Err(RBACError::RelationshipExists)));
+            assert_eq!(server.has_relationship(&member()).unwrap(), false);
+            assert_eq!(server.has_relationship(&viewer()).unwrap(), true);
+
+            // in order, a delete before a touch of the same relationship
+            let changed = server
+                .write_batch(&[
+                    Update::Delete(viewer()),
+                    Update::Touch(viewer()),
+                    Update::Touch(member()),
+                ])
+                .unwrap();
+            assert_eq!(changed, vec![true, true, true]);
+            assert_eq!(server.has_relationship(&viewer()).unwrap(), true);
+        }
+
+        #[test]
+        fn community_typed_check() {
+            let server = rbac::RBAC::new("./rocksdb/test11");
+            server.clear(true).unwrap();
+
+            PostRoles::Writer
+                .grant(&server, &UESR_ALICE, &POST_BY_ALICE)
+                .unwrap();
+            GroupRoles::Member
+                .grant(&server, &USER_BOB, &GROUP_FOO)
+                .unwrap();
+            PostRoles::Viewer
+                .grant(&server, &GROUP_FOO, &POST_BY_ALICE)
+                .unwrap();
+
+            assert_eq!(
+                PostRoles::Viewer
+                    .check(&server, &UESR_ALICE, &POST_BY_ALICE)
+                    .unwrap(),
+                true
+            );
+            assert_eq!(
+                PostRoles::Viewer
+                    .check(&server, &USER_BOB, &POST_BY_ALICE)
+                    .unwrap(),
+                true
+            );
+            assert_eq!(
+                PostRoles::Writer
+                    .check(&server, &USER_BOB, &POST_BY_ALICE)
+                    .unwrap(),
+                false
+            );
+            assert_eq!(
+                GroupRoles::Admin
+                    .check(&server, &USER_BOB, &GROUP_FOO)
+                    .unwrap(),
+                false
+            );
+
+            // `PostRoles::Viewer.check(&server, &USER_BOB, &GROUP_FOO)` doesn't compile, see the
+            // compile_fail doctest of `rbac_macro::Role`
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn community_async() {
+            let server = rbac::RBAC::new("./rocksdb/test9");
+            server.clear(true).unwrap();
+            let server = rbac::nonblocking::AsyncRBAC::new(server);
+
+            let r = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            server.add_relationship(r).await.unwrap();
+
+            // the same instance is shared between tasks
+            let tasks: Vec<_> = [UESR_ALICE, USER_BOB]
+                .into_iter()
+                .map(|user| {
+                    let server = server.clone();
+                    tokio::spawn(async move {
+                        let test =
+                            EntityRelationship::new(&user, &PostRoles::Viewer, &POST_BY_ALICE);
+                        server.allowed(test).await
+                    })
+                })
+                .collect();
+
+            let mut results = Vec::new();
+            for task in tasks {
+                results.push(task.await.unwrap());
+            }
+            assert_eq!(results[0].as_ref().unwrap(), &true);
+            // bob is not a node yet
+            assert!(matches!(results[1], Err(RBACError::VertexNotFound)));
+
+            let objects = server
+                .lookup_objects(UESR_ALICE, PostRoles::Viewer)
+                .await
+                .unwrap();
+            assert_eq!(objects, vec!["Post_1"]);
+
+            let r = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            assert_eq!(server.remove_relationship(r).await.unwrap(), true);
+
+            let subjects = server
+                .lookup_subjects(PostRoles::Writer, POST_BY_ALICE)
+                .await
+                .unwrap();
+            assert!(subjects.is_empty());
+        }
+
+        #[tokio::test]
+        async fn community_async_pool() {
+            let server = rbac::RBAC::new("./rocksdb/test19");
+            server.clear(true).unwrap();
+            let r = EntityRelationship::new(&UESR_ALICE, &PostRoles::Writer, &POST_BY_ALICE);
+            server.add_relationship(&r).unwrap();
+
+            // a single thread works through calls queued from many tasks
+            let server = rbac::nonblocking::AsyncRBAC::with_threads(std::sync::Arc::new(server), 1);
+            let tasks: Vec<_> = (0..8)
+                .map(|_| {
+                    let server = server.clone();
+                    tokio::spawn(async move {
+                        let test = EntityRelationship::new(
+                            &UESR_ALICE,
+                            &PostRoles::Viewer,
+                            &POST_BY_ALICE,
+                        );
+                        server.allowed(test).await
+                    })
+                })
+                .collect();
+
+            for task in tasks {
+                assert_eq!(task.await.unwrap().unwrap(), true);
+            }
+        }
+    }
+
+    mod web {
+        use axum::{
+            body::Body,
+            http::{Method, Request, StatusCode},
+        };
+        use rbac::{nonblocking::AsyncRBAC, EntityRelationship};
+        use tower::ServiceExt;
+
+        use crate::examples::{community::*, web::app};
+
+        fn request(method: Method, uri: &str, user: Option<i32>) -> Request<Body> {
+            let mut builder = Request::builder().method(method).uri(uri);
+            if let Some(user) = user {
+                builder = builder.header("x-user-id", user.to_string());
+            }
+            builder.body(Body::empty()).unwrap()
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn web_require_role() {
+            let server = rbac::RBAC::new("./rocksdb/test10");
+            server.clear(true).unwrap();
+
+            let alice = User {
+                id: 1,
+                nickname: "Alice",
+            };
+            let bob = User {
+                id: 2,
+                nickname: "Bob",
+            };
+            let post = Post {
+                id: 1,
+                author_id: alice.id,
+                title: "Hello, World!",
+            };
+            let group = Group { id: 1, name: "Foo" };
+
+            server
+                .add_relationship(&EntityRelationship::new(&alice, &PostRoles::Writer, &post))
+                .unwrap();
+            server
+                .add_relationship(&EntityRelationship::new(&bob, &GroupRoles::Member, &group))
+                .unwrap();
+            server
+                .add_relationship(&EntityRelationship::new(&group, &PostRoles::Viewer, &post))
+                .unwrap();
+
+            let app = app(AsyncRBAC::new(server));
+            let status = |req: Request<Body>| {
+                let app = app.clone();
+                async move { app.oneshot(req).await.unwrap().status() }
+            };
+
+            // alice writes, bob views through the group
+            assert_eq!(
+                status(request(Method::GET, "/posts/1", Some(1))).await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                status(request(Method::PUT, "/posts/1", Some(1))).await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                status(request(Method::GET, "/posts/1", Some(2))).await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                status(request(Method::PUT, "/posts/1", Some(2))).await,
+                StatusCode::FORBIDDEN
+            );
+            assert_eq!(
+                status(request(Method::GET, "/groups/1", Some(2))).await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                status(request(Method::GET, "/groups/1", Some(1))).await,
+                StatusCode::FORBIDDEN
+            );
+
+            // never seen user
+            assert_eq!(
+                status(request(Method::GET, "/posts/1", Some(3))).await,
+                StatusCode::FORBIDDEN
+            );
+            assert_eq!(
+                status(request(Method::GET, "/posts/1", None)).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(request(Method::GET, "/posts/foo", Some(1))).await,
+                StatusCode::NOT_FOUND
+            );
+        }
     }
 }
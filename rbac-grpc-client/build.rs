@@ -0,0 +1,5 @@
+fn main() -> std::io::Result<()> {
+    tonic_build::configure()
+        .build_server(false)
+        .compile_protos(&["../proto/rbac/v1/rbac.proto"], &["../proto"])
+}
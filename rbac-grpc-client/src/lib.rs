@@ -0,0 +1,10 @@
+// Client stubs generated from `proto/rbac/v1/rbac.proto`, e.g.
+//
+// let mut client = PermissionsServiceClient::connect("http://127.0.0.1:50051").await?;
+pub mod v1 {
+    tonic::include_proto!("rbac.v1");
+}
+
+pub use v1::{
+    permissions_service_client::PermissionsServiceClient, watch_service_client::WatchServiceClient,
+};
@@ -1,22 +1,38 @@
 extern crate proc_macro;
 
+use std::collections::{HashMap, HashSet};
+
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, Path};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Data, DataEnum, DeriveInput, Fields, Ident, Path, Token,
+};
 
 #[proc_macro_derive(Namespace, attributes(roles))]
 pub fn derive_define_namespace(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let name = input.ident;
+    expand_namespace(&input).unwrap_or_else(|e| e.to_compile_error().into())
+}
+
+fn expand_namespace(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
 
-    let variants = if let Data::Enum(namespaces) = input.data {
-        namespaces.variants
-    } else {
-        panic!("Namespace can only be derived for enums");
+    let variants = match &input.data {
+        Data::Enum(namespaces) => &namespaces.variants,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Namespace can only be derived for enums",
+            ))
+        }
     };
 
     let mut to_string_match_arms = vec![];
-    //let mut from_string_match_arms = vec![];
+    let mut from_name_match_arms = vec![];
+    let mut names = vec![];
     let mut variant_roles = vec![];
     let mut get_roles_match_arms = vec![];
 
@@ -27,10 +43,10 @@ pub fn derive_define_namespace(input: TokenStream) -> TokenStream {
         let roles_path = attrs
             .iter()
             .find(|attr| attr.path().is_ident("roles"))
-            .and_then(|attr| parse_attribute(attr));
+            .map(|attr| attr.parse_args::<Path>())
+            .transpose()?;
 
-        if roles_path.is_some() {
-            let roles = roles_path.unwrap();
+        if let Some(roles) = roles_path {
             variant_roles.push((variant_name.clone(), roles.clone()));
 
             /*
@@ -60,30 +76,26 @@ pub fn derive_define_namespace(input: TokenStream) -> TokenStream {
         let variant_match = match &variant.fields {
             Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
                 quote! {
-                    #name::#variant_name(Some(id)) => format!("{}_{}", stringify!(#variant_name), id),
-                    #name::#variant_name(None) => stringify!(#variant_name).to_string(),
+                    #name::#variant_name(Some(id)) => format!("{}_{}", rbac::encode_key_part(stringify!(#variant_name)), rbac::encode_key_part(id)),
+                    #name::#variant_name(None) => rbac::encode_key_part(stringify!(#variant_name)),
                 }
             }
-            _ => panic!("Namespace requires enum variants with exactly one unnamed field"),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "Namespace requires enum variants with exactly one unnamed field",
+                ))
+            }
         };
 
         to_string_match_arms.push(variant_match);
 
-        // impl FromStr snippet
-        // let variant_from_str = match &variant.fields {
-        //     Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
-        //         quote! {
-        //             s if s.starts_with(stringify!(#variant_name)) => {
-        //                 let id = s.split('_').nth(1).unwrap().parse().unwrap();
-        //                 Ok(#name::#variant_name(Some(id)))
-        //             }
-        //             s if s == stringify!(#variant_name) => Ok(#name::#variant_name(None)),
-        //         }
-        //     }
-        //     _ => panic!("Namespace requires enum variants with exactly one unnamed field"),
-        // };
-
-        // from_string_match_arms.push(variant_from_str);
+        // impl ParseNamespace snippet
+        let variant_str = variant_name.to_string();
+        from_name_match_arms.push(quote! {
+            #variant_str => Some(#name::#variant_name(id)),
+        });
+        names.push(variant_str);
     }
 
     // Generate the ToString implementation
@@ -107,18 +119,37 @@ pub fn derive_define_namespace(input: TokenStream) -> TokenStream {
         }
     };
 
-    // let from_string_impl = quote! {
-    //     impl FromStr for #name {
-    //         type Err = ();
+    // stored keys back into namespaces, e.g. `"Post_1".parse::<Namespaces>()`
+    let from_string_impl = quote! {
+        impl rbac::ParseNamespace for #name {
+            const NAMES: &'static [&'static str] = &[#(#names),*];
 
-    //         fn from_str(s: &str) -> Result<Self, ()> {
-    //             match s {
-    //                 #(#from_string_match_arms)*
-    //                 _ => Err(()),
-    //             }
-    //         }
-    //     }
-    // };
+            fn from_name(name: &str, id: Option<String>) -> Option<Self> {
+                match name {
+                    #(#from_name_match_arms)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl std::str::FromStr for #name {
+            type Err = rbac::ParseNodeError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let error = || rbac::ParseNodeError(s.to_string());
+                let (name, id) = <#name as rbac::ParseNamespace>::split_key(s).ok_or_else(error)?;
+                <#name as rbac::ParseNamespace>::from_name(name, id).ok_or_else(error)
+            }
+        }
+
+        impl TryFrom<&str> for #name {
+            type Error = rbac::ParseNodeError;
+
+            fn try_from(s: &str) -> Result<Self, Self::Error> {
+                s.parse()
+            }
+        }
+    };
 
     // let role_to_node_arms = variant_roles.iter().map(|(variant_name, associated_enum)| {
     //     let associated_enum_name = associated_enum.get_ident().unwrap();
@@ -161,26 +192,33 @@ pub fn derive_define_namespace(input: TokenStream) -> TokenStream {
 
     let gen = quote! {
         #to_string_impl
-        //#from_string_impl
+        #from_string_impl
         #get_roles_method
 
         impl NamespaceToStringAndRole for #name {}
     };
 
-    gen.into()
+    Ok(gen.into())
 }
 
 #[proc_macro_derive(ToNode, attributes(namespace))]
 pub fn derive_to_node(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
+    expand_to_node(&input).unwrap_or_else(|e| e.to_compile_error().into())
+}
 
-    let struct_name = input.ident;
+fn expand_to_node(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let struct_name = &input.ident;
     let namespace_path = input
         .attrs
         .iter()
         .find(|attr| attr.path().is_ident("namespace"))
-        .and_then(|attr| parse_attribute(attr))
-        .expect("Expected #[namespace] attribute with a path");
+        .ok_or_else(|| {
+            syn::Error::new_spanned(struct_name, "Expected #[namespace] attribute with a path")
+        })?
+        .parse_args::<Path>()?;
+
+    let namespace_id = namespace_id(&namespace_path)?;
 
     let gen = quote! {
         impl rbac::ToNode for #struct_name {
@@ -188,126 +226,838 @@ pub fn derive_to_node(input: TokenStream) -> TokenStream {
                 Node::new(Box::new(#namespace_path(parent_id)), self.id.to_string())
             }
         }
+
+        impl rbac::InNamespace<#namespace_id> for #struct_name {}
     };
 
-    gen.into()
+    Ok(gen.into())
 }
 
-#[proc_macro_derive(Role, attributes(namespace, child_of))]
+/// Besides the role itself, generates typed `check` and `grant` helpers which only take
+/// objects of the role's namespace:
+///
+/// ```no_run
+/// # use rbac::{
+/// #     NamespaceRole, NamespaceToString, NamespaceToStringAndRole, Node, RoleHierarchy, ToNode,
+/// # };
+/// # use rbac_macro::{Namespace, Role, ToNode};
+/// #
+/// # #[derive(Namespace)]
+/// # pub enum Namespaces {
+/// #     User(Option<String>),
+/// #     #[roles(PostRoles)]
+/// #     Post(Option<String>),
+/// #     #[roles(GroupRoles)]
+/// #     Group(Option<String>),
+/// # }
+/// #
+/// # #[derive(Role, Default)]
+/// # #[namespace(Namespaces::Group)]
+/// # pub enum GroupRoles {
+/// #     #[default]
+/// #     Member,
+/// # }
+/// #
+/// # #[derive(Role, Default)]
+/// # #[namespace(Namespaces::Post)]
+/// # pub enum PostRoles {
+/// #     #[default]
+/// #     Viewer,
+/// # }
+/// #
+/// # #[derive(ToNode)]
+/// # #[namespace(Namespaces::User)]
+/// # pub struct User {
+/// #     pub id: i32,
+/// # }
+/// #
+/// # #[derive(ToNode)]
+/// # #[namespace(Namespaces::Post)]
+/// # pub struct Post {
+/// #     pub id: i32,
+/// # }
+/// #
+/// # #[derive(ToNode)]
+/// # #[namespace(Namespaces::Group)]
+/// # pub struct Group {
+/// #     pub id: i32,
+/// # }
+/// #
+/// let server = rbac::RBAC::new("./rocksdb/doc");
+/// let (bob, post, group) = (User { id: 2 }, Post { id: 1 }, Group { id: 1 });
+/// PostRoles::Viewer.check(&server, &bob, &post).unwrap();
+/// # let _ = group;
+/// ```
+///
+/// while an object of another namespace doesn't compile:
+///
+/// ```compile_fail,E0277
+/// # use rbac::{
+/// #     NamespaceRole, NamespaceToString, NamespaceToStringAndRole, Node, RoleHierarchy, ToNode,
+/// # };
+/// # use rbac_macro::{Namespace, Role, ToNode};
+/// #
+/// # #[derive(Namespace)]
+/// # pub enum Namespaces {
+/// #     User(Option<String>),
+/// #     #[roles(PostRoles)]
+/// #     Post(Option<String>),
+/// #     #[roles(GroupRoles)]
+/// #     Group(Option<String>),
+/// # }
+/// #
+/// # #[derive(Role, Default)]
+/// # #[namespace(Namespaces::Group)]
+/// # pub enum GroupRoles {
+/// #     #[default]
+/// #     Member,
+/// # }
+/// #
+/// # #[derive(Role, Default)]
+/// # #[namespace(Namespaces::Post)]
+/// # pub enum PostRoles {
+/// #     #[default]
+/// #     Viewer,
+/// # }
+/// #
+/// # #[derive(ToNode)]
+/// # #[namespace(Namespaces::User)]
+/// # pub struct User {
+/// #     pub id: i32,
+/// # }
+/// #
+/// # #[derive(ToNode)]
+/// # #[namespace(Namespaces::Post)]
+/// # pub struct Post {
+/// #     pub id: i32,
+/// # }
+/// #
+/// # #[derive(ToNode)]
+/// # #[namespace(Namespaces::Group)]
+/// # pub struct Group {
+/// #     pub id: i32,
+/// # }
+/// #
+/// let server = rbac::RBAC::new("./rocksdb/doc");
+/// let (bob, post, group) = (User { id: 2 }, Post { id: 1 }, Group { id: 1 });
+/// PostRoles::Viewer.check(&server, &bob, &group).unwrap();
+/// ```
+///
+/// and neither does an object of a same named variant of another namespace enum:
+///
+/// ```compile_fail,E0277
+/// # use rbac::{
+/// #     NamespaceRole, NamespaceToString, NamespaceToStringAndRole, Node, RoleHierarchy, ToNode,
+/// # };
+/// # use rbac_macro::{Namespace, Role, ToNode};
+/// #
+/// # #[derive(Namespace)]
+/// # pub enum Blog {
+/// #     User(Option<String>),
+/// #     #[roles(PostRoles)]
+/// #     Post(Option<String>),
+/// # }
+/// #
+/// # #[derive(Namespace)]
+/// # pub enum Forum {
+/// #     Post(Option<String>),
+/// # }
+/// #
+/// # #[derive(Role, Default)]
+/// # #[namespace(Blog::Post)]
+/// # pub enum PostRoles {
+/// #     #[default]
+/// #     Viewer,
+/// # }
+/// #
+/// # #[derive(ToNode)]
+/// # #[namespace(Blog::User)]
+/// # pub struct User {
+/// #     pub id: i32,
+/// # }
+/// #
+/// # #[derive(ToNode)]
+/// # #[namespace(Forum::Post)]
+/// # pub struct Thread {
+/// #     pub id: i32,
+/// # }
+/// #
+/// let server = rbac::RBAC::new("./rocksdb/doc");
+/// let (bob, thread) = (User { id: 2 }, Thread { id: 1 });
+/// PostRoles::Viewer.check(&server, &bob, &thread).unwrap();
+/// ```
+#[proc_macro_derive(Role, attributes(namespace, child_of, membership, implied_by))]
 pub fn derive_role(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
+    expand_role(&input).unwrap_or_else(|e| e.to_compile_error().into())
+}
+
+fn expand_role(input: &DeriveInput) -> syn::Result<TokenStream> {
     let name = &input.ident;
 
     let namespace_path = input
         .attrs
         .iter()
         .find(|attr| attr.path().is_ident("namespace"))
-        .and_then(parse_attribute)
-        .expect("Expected #[namespace] attribute with a valid path");
+        .ok_or_else(|| {
+            syn::Error::new_spanned(name, "Expected #[namespace] attribute with a valid path")
+        })?
+        .parse_args::<Path>()?;
+
+    let data_enum = match &input.data {
+        Data::Enum(data_enum) => data_enum,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Role derive macro only works with enums",
+            ))
+        }
+    };
+
+    if data_enum.variants.is_empty() {
+        return Ok(TokenStream::new());
+    }
+
+    let hierarchy = parse_hierarchy(data_enum)?;
+    let implied = parse_implied(data_enum)?;
+
+    let to_string_arms = data_enum.variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        let variant_str = variant_name.to_string();
+        quote! {
+            #name::#variant_name => #variant_str.to_string(),
+        }
+    });
 
-    if let Data::Enum(data_enum) = input.data {
-        if data_enum.variants.len() == 0 {
-            return TokenStream::new();
+    let from_str_arms = data_enum.variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        let variant_str = variant_name.to_string();
+        quote! {
+            #variant_str => Ok(#name::#variant_name),
         }
+    });
 
-        let to_string_arms = data_enum.variants.iter().map(|variant| {
+    let to_node_arms = data_enum.variants.iter().map(|variant| {
             let variant_name = &variant.ident;
-            let variant_str = variant_name.to_string();
             quote! {
-                #name::#variant_name => #variant_str.to_string(),
+                #name::#variant_name => Node::new(Box::new(#namespace_path(group_id.clone())), self.to_string()),
             }
         });
 
-        let to_node_arms = data_enum.variants.iter().map(|variant| {
-            let variant_name = &variant.ident;
+    /*
+    #[derive(Role)]
+    #[namespace(Namespaces::Group)]
+    pub enum GroupRoles {
+        Admin,
+        #[child_of(Admin)]
+        Member,
+    }
+    impl GroupRoles {
+        pub fn iter_hierarchy(mut f: impl FnMut(Self, Self)) {
+            f(GroupRoles::Admin, GroupRoles::Member);
+        }
+    }
+    */
+
+    let child_of_arms = hierarchy.iter().map(|(parent, child)| {
+        quote! {
+            f(Box::new(#name::#parent), Box::new(#name::#child));
+        }
+    });
+
+    let implied_arms = implied.iter().map(
+        |ImpliedBy {
+             role,
+             implied_by,
+             via,
+         }| {
             quote! {
-                #name::#variant_name => Node::new(Box::new(#namespace_path(group_id.clone())), self.to_string()),
+                f(Box::new(#name::#role), Box::new(#implied_by), Box::new(#name::#via));
             }
-        });
+        },
+    );
 
-        /*
-        #[derive(Role)]
-        #[namespace(Namespaces::Group)]
-        pub enum GroupRoles {
-            Admin,
-            #[child_of(Admin)]
-            Member,
+    // holders of a #[membership] role act as the entity itself, e.g. group members
+    let is_membership_arms = data_enum.variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        let membership = variant
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("membership"));
+
+        quote! {
+            #name::#variant_name => #membership,
+        }
+    });
+
+    let iter_al_arms = data_enum.variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+
+        quote! {
+            f(Box::new(#name::#variant_name));
         }
-        impl GroupRoles {
-            pub fn iter_hierarchy(mut f: impl FnMut(Self, Self)) {
-                f(GroupRoles::Admin, GroupRoles::Member);
+    });
+
+    let namespace_id = namespace_id(&namespace_path)?;
+
+    let expanded = quote! {
+        impl #name {
+            pub fn to_string(&self) -> String {
+                match self {
+                    #(#to_string_arms)*
+                }
+            }
+
+            // e.g. `PostRoles::Viewer.check(&server, &user, &post)`, the object has to be
+            // an entity of this role's namespace
+            pub fn check<O: rbac::InNamespace<#namespace_id>>(
+                &self,
+                server: &rbac::RBAC,
+                subject: &dyn rbac::ToNode,
+                object: &O,
+            ) -> Result<bool, rbac::RBACError> {
+                server.allowed(&rbac::EntityRelationship::new(subject, self, object))
+            }
+
+            pub fn grant<O: rbac::InNamespace<#namespace_id>>(
+                &self,
+                server: &rbac::RBAC,
+                subject: &dyn rbac::ToNode,
+                object: &O,
+            ) -> Result<bool, rbac::RBACError> {
+                server.add_relationship(&rbac::EntityRelationship::new(subject, self, object))
             }
         }
-        */
 
-        let child_of_arms = data_enum.variants.iter().map(|variant| {
-            let variant_name = &variant.ident;
-            let child_of = variant
-                .attrs
-                .iter()
-                .find(|attr| attr.path().is_ident("child_of"));
+        impl RoleHierarchy for #name {
+            fn iter_hierarchy(&self, f: &mut dyn FnMut(Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>)) {
+                #(#child_of_arms)*
+            }
+
+            fn iter_implied(&self, f: &mut dyn FnMut(Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>)) {
+                #(#implied_arms)*
+            }
 
-            if child_of.is_none() {
-                return quote! {};
+            fn iter_all(&self, f: &mut dyn FnMut(Box<dyn RoleHierarchy>)) {
+                #(#iter_al_arms)*
             }
 
-            let child_of = child_of.unwrap();
+            fn is_membership(&self) -> bool {
+                match self {
+                    #(#is_membership_arms)*
+                }
+            }
 
-            let parent = parse_attribute(child_of)
-                .expect("Expected #[child_of] attribute with a valid path");
+            // fn as_any(&self) -> &dyn Any {
+            //     self
+            // }
+        }
 
-            quote! {
-                f(Box::new(#name::#parent), Box::new(#name::#variant_name));
+        impl std::str::FromStr for #name {
+            type Err = rbac::ParseNodeError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    #(#from_str_arms)*
+                    _ => Err(rbac::ParseNodeError(s.to_string())),
+                }
             }
-        });
+        }
 
-        let iter_al_arms = data_enum.variants.iter().map(|variant| {
-            let variant_name = &variant.ident;
+        impl TryFrom<&str> for #name {
+            type Error = rbac::ParseNodeError;
 
-            quote! {
-                f(Box::new(#name::#variant_name));
+            fn try_from(s: &str) -> Result<Self, Self::Error> {
+                s.parse()
             }
-        });
+        }
 
-        let expanded = quote! {
-            impl #name {
-                pub fn to_string(&self) -> String {
-                    match self {
-                        #(#to_string_arms)*
-                    }
+        impl ToNode for #name {
+            fn to_node(&self, group_id: Option<String>) -> Node {
+                match self {
+                    #(#to_node_arms)*
                 }
             }
+        }
+    };
 
-            impl RoleHierarchy for #name {
-                fn iter_hierarchy(&self, f: &mut dyn FnMut(Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>)) {
-                    #(#child_of_arms)*
+    Ok(TokenStream::from(expanded))
+}
+
+// `(parent, child)` pairs from `#[child_of(Parent, ...)]`, rejecting parents that aren't variants of
+// the enum and cycles, which would otherwise only show up at runtime
+fn parse_hierarchy(data_enum: &DataEnum) -> syn::Result<Vec<(Ident, Ident)>> {
+    let variants: Vec<&Ident> = data_enum.variants.iter().map(|v| &v.ident).collect();
+
+    let mut hierarchy = vec![];
+    let mut spans = HashMap::new();
+    let mut errors: Option<syn::Error> = None;
+    let mut push_error = |e: syn::Error| match &mut errors {
+        Some(errors) => errors.combine(e),
+        None => errors = Some(e),
+    };
+
+    for variant in &data_enum.variants {
+        let child_of = variant
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("child_of"));
+
+        // `#[child_of(A, B)]` and `#[child_of(A)] #[child_of(B)]` are the same
+        for child_of in child_of {
+            let parents =
+                match child_of.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated) {
+                    Ok(parents) if !parents.is_empty() => parents,
+                    _ => {
+                        push_error(syn::Error::new_spanned(
+                            child_of,
+                            "Expected #[child_of(Role, ...)] naming variants of this enum",
+                        ));
+                        continue;
+                    }
+                };
+
+            for parent in parents {
+                let edge = (parent.clone(), variant.ident.clone());
+
+                if parent == variant.ident {
+                    push_error(syn::Error::new_spanned(
+                        &parent,
+                        format!("role `{}` can't be a child of itself", parent),
+                    ));
+                } else if !variants.contains(&&parent) {
+                    let names: Vec<String> = variants.iter().map(|v| format!("`{}`", v)).collect();
+                    push_error(syn::Error::new_spanned(
+                        &parent,
+                        format!(
+                            "unknown role `{}`, expected one of {}",
+                            parent,
+                            names.join(", ")
+                        ),
+                    ));
+                } else if spans.contains_key(&edge) {
+                    push_error(syn::Error::new_spanned(
+                        &parent,
+                        format!(
+                            "role `{}` is already a parent of `{}`",
+                            parent, variant.ident
+                        ),
+                    ));
+                } else {
+                    spans.insert(edge.clone(), parent);
+                    hierarchy.push(edge);
                 }
+            }
+        }
+    }
+
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    if let Some(cycle) = find_cycle(&variants, &hierarchy) {
+        // reported on the parent closing the cycle
+        let closing = (
+            cycle[cycle.len() - 2].clone(),
+            cycle[cycle.len() - 1].clone(),
+        );
+        let path: Vec<String> = cycle.iter().map(|v| v.to_string()).collect();
+        return Err(syn::Error::new_spanned(
+            &spans[&closing],
+            format!("role hierarchy has a cycle: {}", path.join(" -> ")),
+        ));
+    }
+
+    Ok(hierarchy)
+}
+
+// `#[implied_by(GroupRoles::Admin, via = OwnedBy)]` on `role`: admins of a group holding
+// `OwnedBy` on an object also hold `role` on it
+struct ImpliedBy {
+    role: Ident,
+    implied_by: Path,
+    via: Ident,
+}
+
+// the `GroupRoles::Admin, via = OwnedBy` part
+struct ImpliedByArgs {
+    implied_by: Path,
+    via: Ident,
+}
+
+impl Parse for ImpliedByArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let implied_by = input.parse()?;
+        input.parse::<Token![,]>()?;
 
-                fn iter_all(&self, f: &mut dyn FnMut(Box<dyn RoleHierarchy>)) {
-                    #(#iter_al_arms)*
+        let key: Ident = input.parse()?;
+        if key != "via" {
+            return Err(syn::Error::new_spanned(key, "Expected `via = Role`"));
+        }
+        input.parse::<Token![=]>()?;
+        let via = input.parse()?;
+
+        Ok(ImpliedByArgs { implied_by, via })
+    }
+}
+
+// the `via` role has to be a variant of this enum, `implied_by` is checked by the compiler
+fn parse_implied(data_enum: &DataEnum) -> syn::Result<Vec<ImpliedBy>> {
+    let variants: Vec<&Ident> = data_enum.variants.iter().map(|v| &v.ident).collect();
+
+    let mut implied = vec![];
+    let mut errors: Option<syn::Error> = None;
+    let mut push_error = |e: syn::Error| match &mut errors {
+        Some(errors) => errors.combine(e),
+        None => errors = Some(e),
+    };
+
+    for variant in &data_enum.variants {
+        let attrs = variant
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("implied_by"));
+
+        for attr in attrs {
+            let ImpliedByArgs { implied_by, via } = match attr.parse_args::<ImpliedByArgs>() {
+                Ok(args) => args,
+                Err(_) => {
+                    push_error(syn::Error::new_spanned(
+                        attr,
+                        "Expected #[implied_by(OtherRoles::Role, via = Role)]",
+                    ));
+                    continue;
                 }
+            };
 
-                // fn as_any(&self) -> &dyn Any {
-                //     self
-                // }
+            if !variants.contains(&&via) {
+                let names: Vec<String> = variants.iter().map(|v| format!("`{}`", v)).collect();
+                push_error(syn::Error::new_spanned(
+                    &via,
+                    format!(
+                        "unknown role `{}`, expected one of {}",
+                        via,
+                        names.join(", ")
+                    ),
+                ));
+                continue;
             }
 
-            impl ToNode for #name {
-                fn to_node(&self, group_id: Option<String>) -> Node {
-                    match self {
-                        #(#to_node_arms)*
-                    }
+            implied.push(ImpliedBy {
+                role: variant.ident.clone(),
+                implied_by,
+                via,
+            });
+        }
+    }
+
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(implied),
+    }
+}
+
+// dfs from every role down to its children, returns the roles of the first cycle found with
+// the first one repeated at the end
+fn find_cycle(variants: &[&Ident], hierarchy: &[(Ident, Ident)]) -> Option<Vec<Ident>> {
+    fn visit(
+        role: &Ident,
+        hierarchy: &[(Ident, Ident)],
+        stack: &mut Vec<Ident>,
+        done: &mut HashSet<Ident>,
+    ) -> Option<Vec<Ident>> {
+        if let Some(i) = stack.iter().position(|r| r == role) {
+            let mut cycle = stack[i..].to_vec();
+            cycle.push(role.clone());
+            return Some(cycle);
+        }
+        if done.contains(role) {
+            return None;
+        }
+
+        stack.push(role.clone());
+        for (_, child) in hierarchy.iter().filter(|(parent, _)| parent == role) {
+            if let Some(cycle) = visit(child, hierarchy, stack, done) {
+                return Some(cycle);
+            }
+        }
+        stack.pop();
+        done.insert(role.clone());
+
+        None
+    }
+
+    let mut done = HashSet::new();
+    variants
+        .iter()
+        .find_map(|role| visit(role, hierarchy, &mut vec![], &mut done))
+}
+
+// Type level id of a namespace variant for `rbac::InNamespace`: FNV-1a of `Enum::Variant`, so
+// same named variants of two namespace enums don't share an id.
+fn namespace_id(path: &Path) -> syn::Result<u64> {
+    let mut segments = path.segments.iter().rev();
+    let (variant, namespace) = match (segments.next(), segments.next()) {
+        (Some(variant), Some(namespace)) => (&variant.ident, &namespace.ident),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                path,
+                "Expected a namespace variant path, e.g. `Namespace::Post`",
+            ))
+        }
+    };
+
+    Ok(format!("{}::{}", namespace, variant)
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hierarchy(input: DeriveInput) -> syn::Result<Vec<(String, String)>> {
+        let data_enum = match input.data {
+            Data::Enum(data_enum) => data_enum,
+            _ => unreachable!(),
+        };
+
+        Ok(parse_hierarchy(&data_enum)?
+            .into_iter()
+            .map(|(parent, child)| (parent.to_string(), child.to_string()))
+            .collect())
+    }
+
+    #[test]
+    fn role_hierarchy() {
+        let input: DeriveInput = syn::parse_quote! {
+            enum GroupRoles {
+                Admin,
+                #[child_of(Admin)]
+                Member,
+            }
+        };
+        assert_eq!(
+            hierarchy(input).unwrap(),
+            vec![("Admin".to_string(), "Member".to_string())]
+        );
+
+        // both forms, one pair per parent
+        let input: DeriveInput = syn::parse_quote! {
+            enum PostRoles {
+                Owner,
+                #[child_of(Owner)]
+                Moderator,
+                #[child_of(Owner)]
+                Writer,
+                #[child_of(Moderator, Writer)]
+                Commenter,
+                #[child_of(Moderator)]
+                #[child_of(Commenter)]
+                Viewer,
+            }
+        };
+        let pairs: Vec<(&str, &str)> = vec![
+            ("Owner", "Moderator"),
+            ("Owner", "Writer"),
+            ("Moderator", "Commenter"),
+            ("Writer", "Commenter"),
+            ("Moderator", "Viewer"),
+            ("Commenter", "Viewer"),
+        ];
+        assert_eq!(
+            hierarchy(input).unwrap(),
+            pairs
+                .into_iter()
+                .map(|(parent, child)| (parent.to_string(), child.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn role_implied_by() {
+        let input: DeriveInput = syn::parse_quote! {
+            enum PostRoles {
+                #[implied_by(GroupRoles::Admin, via = OwnedBy)]
+                #[implied_by(TeamRoles::Lead, via = OwnedBy)]
+                Writer,
+                OwnedBy,
+            }
+        };
+        let data_enum = match input.data {
+            Data::Enum(data_enum) => data_enum,
+            _ => unreachable!(),
+        };
+        let implied: Vec<String> = parse_implied(&data_enum)
+            .unwrap()
+            .into_iter()
+            .map(
+                |ImpliedBy {
+                     role,
+                     implied_by,
+                     via,
+                 }| { format!("{} {} {}", role, quote!(#implied_by), via) },
+            )
+            .collect();
+        assert_eq!(
+            implied,
+            vec![
+                "Writer GroupRoles :: Admin OwnedBy",
+                "Writer TeamRoles :: Lead OwnedBy"
+            ]
+        );
+
+        for input in [
+            syn::parse_quote! {
+                enum PostRoles {
+                    #[implied_by(GroupRoles::Admin, via = Owner)]
+                    Writer,
+                }
+            },
+            syn::parse_quote! {
+                enum PostRoles {
+                    #[implied_by(GroupRoles::Admin)]
+                    Writer,
+                }
+            },
+            syn::parse_quote! {
+                enum PostRoles {
+                    #[implied_by(GroupRoles::Admin, through = Writer)]
+                    Writer,
                 }
+            },
+        ] {
+            let input: DeriveInput = input;
+            let data_enum = match input.data {
+                Data::Enum(data_enum) => data_enum,
+                _ => unreachable!(),
+            };
+            assert!(parse_implied(&data_enum).is_err());
+        }
+    }
+
+    #[test]
+    fn role_hierarchy_errors() {
+        let input: DeriveInput = syn::parse_quote! {
+            enum Roles {
+                #[child_of(Owner)]
+                Admin,
+            }
+        };
+        let e = hierarchy(input).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "unknown role `Owner`, expected one of `Admin`"
+        );
+
+        let input: DeriveInput = syn::parse_quote! {
+            enum Roles {
+                #[child_of(Admin)]
+                Admin,
+            }
+        };
+        let e = hierarchy(input).unwrap_err();
+        assert_eq!(e.to_string(), "role `Admin` can't be a child of itself");
+
+        let input: DeriveInput = syn::parse_quote! {
+            enum Roles {
+                Owner,
+                #[child_of(Owner)]
+                #[child_of(Owner)]
+                Admin,
+            }
+        };
+        let e = hierarchy(input).unwrap_err();
+        assert_eq!(e.to_string(), "role `Owner` is already a parent of `Admin`");
+
+        let input: DeriveInput = syn::parse_quote! {
+            enum Roles {
+                Owner,
+                #[child_of()]
+                Admin,
+            }
+        };
+        assert!(hierarchy(input).is_err());
+
+        let input: DeriveInput = syn::parse_quote! {
+            enum Roles {
+                Owner,
+                #[child_of(Member)]
+                Admin,
+                #[child_of(Admin)]
+                Member,
+            }
+        };
+        let e = hierarchy(input).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "role hierarchy has a cycle: Admin -> Member -> Admin"
+        );
+
+        let input: DeriveInput = syn::parse_quote! {
+            enum Roles {
+                #[child_of(Member)]
+                Owner,
+                #[child_of(Owner)]
+                Admin,
+                #[child_of(Owner, Admin)]
+                Member,
+            }
+        };
+        let e = hierarchy(input).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "role hierarchy has a cycle: Owner -> Admin -> Member -> Owner"
+        );
+    }
+
+    #[test]
+    fn derive_errors() {
+        let input: DeriveInput = syn::parse_quote! {
+            struct Namespaces;
+        };
+        let e = expand_namespace(&input).unwrap_err();
+        assert_eq!(e.to_string(), "Namespace can only be derived for enums");
+
+        let input: DeriveInput = syn::parse_quote! {
+            enum Namespaces {
+                User(Option<String>),
+                Post,
             }
         };
+        let e = expand_namespace(&input).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Namespace requires enum variants with exactly one unnamed field"
+        );
 
-        TokenStream::from(expanded)
-    } else {
-        panic!("Role derive macro only works with enums");
+        let input: DeriveInput = syn::parse_quote! {
+            struct User {
+                id: i32,
+            }
+        };
+        let e = expand_to_node(&input).unwrap_err();
+        assert_eq!(e.to_string(), "Expected #[namespace] attribute with a path");
     }
-}
 
-// `#[namespace(AnotherNamespaces::User)]` 형태의 경로를 파싱
-fn parse_attribute(attr: &Attribute) -> Option<Path> {
-    attr.parse_args::<Path>().ok()
+    #[test]
+    fn namespace_ids() {
+        let blog: Path = syn::parse_quote!(Blog::Post);
+        let forum: Path = syn::parse_quote!(Forum::Post);
+        let qualified: Path = syn::parse_quote!(crate::Blog::Post);
+        assert_ne!(namespace_id(&blog).unwrap(), namespace_id(&forum).unwrap());
+        assert_eq!(
+            namespace_id(&blog).unwrap(),
+            namespace_id(&qualified).unwrap()
+        );
+
+        let bare: Path = syn::parse_quote!(Post);
+        let e = namespace_id(&bare).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Expected a namespace variant path, e.g. `Namespace::Post`"
+        );
+    }
 }
@@ -0,0 +1,333 @@
+use std::{collections::HashSet, pin::Pin};
+
+use rbac::RBACError;
+use tokio::net::TcpListener;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream, TcpListenerStream},
+    Stream, StreamExt,
+};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{ApiError, AppState, Applied, Change, EntityRef, Operation, Relationship};
+
+pub mod pb {
+    tonic::include_proto!("rbac.v1");
+}
+
+use pb::{
+    check_permission_response::Permissionship,
+    permissions_service_server::{PermissionsService, PermissionsServiceServer},
+    relationship_update,
+    watch_service_server::{WatchService, WatchServiceServer},
+};
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+// gRPC frontend over the same state as the http api, see `proto/rbac/v1/rbac.proto`
+#[derive(Clone)]
+pub struct GrpcService {
+    state: AppState,
+}
+
+impl GrpcService {
+    pub fn new(state: AppState) -> Self {
+        GrpcService { state }
+    }
+}
+
+pub async fn serve(listener: TcpListener, state: AppState) -> Result<(), tonic::transport::Error> {
+    let service = GrpcService::new(state);
+
+    Server::builder()
+        .add_service(PermissionsServiceServer::new(service.clone()))
+        .add_service(WatchServiceServer::new(service))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
+
+impl From<ApiError> for Status {
+    fn from(e: ApiError) -> Self {
+        match e {
+            ApiError::Schema(e) => Status::invalid_argument(format!("{:?}", e)),
+            ApiError::InvalidRequest(e) => Status::invalid_argument(e),
+            ApiError::Rbac(RBACError::VertexNotFound) => {
+                Status::not_found(format!("{:?}", RBACError::VertexNotFound))
+            }
+            ApiError::Rbac(e @ RBACError::DepthLimitExceeded(_)) => {
+                Status::failed_precondition(format!("{:?}", e))
+            }
+            ApiError::Rbac(e) => Status::internal(format!("{:?}", e)),
+            ApiError::UnknownKey(key) => {
+                Status::internal(format!("stored key {} doesn't map back to an entity", key))
+            }
+        }
+    }
+}
+
+// conversions between the wire types
+
+fn entity_ref(object: Option<pb::ObjectReference>, field: &str) -> Result<EntityRef, ApiError> {
+    let object =
+        object.ok_or_else(|| ApiError::InvalidRequest(format!("missing field `{}`", field)))?;
+
+    Ok(EntityRef {
+        namespace: object.object_type,
+        id: object.object_id,
+    })
+}
+
+fn subject_ref(subject: Option<pb::SubjectReference>) -> Result<EntityRef, ApiError> {
+    entity_ref(subject.and_then(|s| s.object), "subject")
+}
+
+fn relationship(r: Option<pb::Relationship>) -> Result<Relationship, ApiError> {
+    let r = r.ok_or_else(|| ApiError::InvalidRequest("missing field `relationship`".into()))?;
+
+    Ok(Relationship {
+        subject: subject_ref(r.subject)?,
+        role: r.relation,
+        object: entity_ref(r.resource, "resource")?,
+    })
+}
+
+fn object_reference(entity: EntityRef) -> pb::ObjectReference {
+    pb::ObjectReference {
+        object_type: entity.namespace,
+        object_id: entity.id,
+    }
+}
+
+fn to_update(change: Change) -> pb::RelationshipUpdate {
+    let operation = match change.operation {
+        Operation::Touch => relationship_update::Operation::Touch,
+        Operation::Delete => relationship_update::Operation::Delete,
+    };
+
+    pb::RelationshipUpdate {
+        operation: operation.into(),
+        relationship: Some(pb::Relationship {
+            resource: Some(object_reference(change.relationship.object)),
+            relation: change.relationship.role,
+            subject: Some(pb::SubjectReference {
+                object: Some(object_reference(change.relationship.subject)),
+            }),
+        }),
+    }
+}
+
+impl GrpcService {
+    // like spicedb, a relationship may only be updated once per request, so the outcome
+    // doesn't depend on the order updates are applied in
+    async fn write(&self, req: pb::WriteRelationshipsRequest) -> Result<Applied, ApiError> {
+        let mut updates = Vec::new();
+        let mut seen = HashSet::new();
+
+        for update in req.updates {
+            let operation = match update.operation() {
+                relationship_update::Operation::Touch => Operation::Touch,
+                relationship_update::Operation::Delete => Operation::Delete,
+                relationship_update::Operation::Unspecified => {
+                    return Err(ApiError::InvalidRequest("unspecified operation".into()))
+                }
+            };
+            let r = relationship(update.relationship)?;
+
+            if !seen.insert(r.clone()) {
+                return Err(ApiError::InvalidRequest(format!(
+                    "duplicate update of {}:{}#{}@{}:{}",
+                    r.object.namespace, r.object.id, r.role, r.subject.namespace, r.subject.id
+                )));
+            }
+            updates.push((operation, r));
+        }
+
+        self.state.apply(updates).await
+    }
+
+    async fn delete(&self, req: pb::DeleteRelationshipsRequest) -> Result<Applied, ApiError> {
+        let updates = req
+            .relationships
+            .into_iter()
+            .map(|r| Ok((Operation::Delete, relationship(Some(r))?)))
+            .collect::<Result<Vec<_>, ApiError>>()?;
+
+        self.state.apply(updates).await
+    }
+
+    async fn check(&self, req: pb::CheckPermissionRequest) -> Result<bool, ApiError> {
+        let r = Relationship {
+            subject: subject_ref(req.subject)?,
+            role: req.permission,
+            object: entity_ref(req.resource, "resource")?,
+        };
+
+        // same as http, never seen subjects or objects are denied
+        match self.state.rbac.allowed(self.state.relationship(&r)?).await {
+            Ok(allowed) => Ok(allowed),
+            Err(RBACError::VertexNotFound) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn find_resources(
+        &self,
+        req: pb::LookupResourcesRequest,
+    ) -> Result<Vec<pb::LookupResourcesResponse>, ApiError> {
+        let subject = subject_ref(req.subject)?;
+        let subject = self.state.schema.entity(&subject.namespace, &subject.id)?;
+        let role = self
+            .state
+            .schema
+            .role(&req.resource_object_type, &req.permission)?;
+
+        let objects = self.state.rbac.lookup_objects(subject, role).await?;
+
+        // an object that can't be named fails the lookup rather than going missing from it
+        objects
+            .iter()
+            .map(|key| {
+                Ok(pb::LookupResourcesResponse {
+                    resource_object_id: self.state.entity_ref(key)?.id,
+                })
+            })
+            .collect()
+    }
+
+    async fn find_subjects(
+        &self,
+        req: pb::LookupSubjectsRequest,
+    ) -> Result<Vec<pb::LookupSubjectsResponse>, ApiError> {
+        let resource = entity_ref(req.resource, "resource")?;
+        let role = self
+            .state
+            .schema
+            .role(&resource.namespace, &req.permission)?;
+        let object = self
+            .state
+            .schema
+            .entity(&resource.namespace, &resource.id)?;
+
+        let subjects = self.state.rbac.lookup_subjects(role, object).await?;
+
+        let subjects = subjects
+            .iter()
+            .map(|key| self.state.entity_ref(key))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(subjects
+            .into_iter()
+            .filter(|entity| {
+                req.subject_object_type.is_empty() || entity.namespace == req.subject_object_type
+            })
+            .map(|entity| pb::LookupSubjectsResponse {
+                subject: Some(pb::SubjectReference {
+                    object: Some(object_reference(entity)),
+                }),
+            })
+            .collect())
+    }
+}
+
+#[tonic::async_trait]
+impl PermissionsService for GrpcService {
+    type LookupResourcesStream = ResponseStream<pb::LookupResourcesResponse>;
+    type LookupSubjectsStream = ResponseStream<pb::LookupSubjectsResponse>;
+
+    async fn write_relationships(
+        &self,
+        request: Request<pb::WriteRelationshipsRequest>,
+    ) -> Result<Response<pb::WriteRelationshipsResponse>, Status> {
+        let applied = self.write(request.into_inner()).await?;
+
+        Ok(Response::new(pb::WriteRelationshipsResponse {
+            revision: applied.revision,
+        }))
+    }
+
+    async fn delete_relationships(
+        &self,
+        request: Request<pb::DeleteRelationshipsRequest>,
+    ) -> Result<Response<pb::DeleteRelationshipsResponse>, Status> {
+        let applied = self.delete(request.into_inner()).await?;
+        let deleted = u32::try_from(applied.count).map_err(|_| {
+            Status::out_of_range("more relationships deleted than fit the response")
+        })?;
+
+        Ok(Response::new(pb::DeleteRelationshipsResponse {
+            deleted,
+            revision: applied.revision,
+        }))
+    }
+
+    async fn check_permission(
+        &self,
+        request: Request<pb::CheckPermissionRequest>,
+    ) -> Result<Response<pb::CheckPermissionResponse>, Status> {
+        let permissionship = if self.check(request.into_inner()).await? {
+            Permissionship::HasPermission
+        } else {
+            Permissionship::NoPermission
+        };
+
+        Ok(Response::new(pb::CheckPermissionResponse {
+            permissionship: permissionship.into(),
+        }))
+    }
+
+    async fn lookup_resources(
+        &self,
+        request: Request<pb::LookupResourcesRequest>,
+    ) -> Result<Response<Self::LookupResourcesStream>, Status> {
+        let resources = self.find_resources(request.into_inner()).await?;
+
+        Ok(Response::new(Box::pin(tokio_stream::iter(
+            resources.into_iter().map(Ok),
+        ))))
+    }
+
+    async fn lookup_subjects(
+        &self,
+        request: Request<pb::LookupSubjectsRequest>,
+    ) -> Result<Response<Self::LookupSubjectsStream>, Status> {
+        let subjects = self.find_subjects(request.into_inner()).await?;
+
+        Ok(Response::new(Box::pin(tokio_stream::iter(
+            subjects.into_iter().map(Ok),
+        ))))
+    }
+}
+
+#[tonic::async_trait]
+impl WatchService for GrpcService {
+    type WatchStream = ResponseStream<pb::WatchResponse>;
+
+    async fn watch(
+        &self,
+        request: Request<pb::WatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let object_types = request.into_inner().object_types;
+
+        let changes =
+            BroadcastStream::new(self.state.subscribe()).filter_map(move |change| match change {
+                Ok(change) => {
+                    if !object_types.is_empty()
+                        && !object_types.contains(&change.relationship.object.namespace)
+                    {
+                        return None;
+                    }
+
+                    let revision = change.revision;
+                    Some(Ok(pb::WatchResponse {
+                        updates: vec![to_update(change)],
+                        revision,
+                    }))
+                }
+                Err(BroadcastStreamRecvError::Lagged(n)) => Some(Err(Status::data_loss(format!(
+                    "watcher fell behind and missed {} changes",
+                    n
+                )))),
+            });
+
+        Ok(Response::new(Box::pin(changes)))
+    }
+}
@@ -0,0 +1,373 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use rbac::{
+    nonblocking::AsyncRBAC,
+    schema::{Schema, SchemaError},
+    EntityRelationship, RBACError, Update, RBAC,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, Mutex},
+};
+
+pub mod grpc;
+
+// how many unread changes a slow watcher may lag behind before it starts missing some
+const WATCH_CAPACITY: usize = 1024;
+
+// shared by the http and grpc frontends
+#[derive(Clone)]
+pub struct AppState {
+    rbac: AsyncRBAC,
+    schema: Arc<Schema>,
+    changes: broadcast::Sender<Change>,
+    // changes applied by this process, it isn't stored and starts over on a restart
+    revision: Arc<AtomicU64>,
+    // held while a batch is written and its revisions are taken
+    writes: Arc<Mutex<()>>,
+}
+
+impl AppState {
+    pub fn new(rbac: RBAC, schema: Schema) -> Self {
+        let (changes, _) = broadcast::channel(WATCH_CAPACITY);
+        AppState {
+            rbac: AsyncRBAC::new(rbac),
+            schema: Arc::new(schema),
+            changes,
+            revision: Arc::new(AtomicU64::new(0)),
+            writes: Arc::new(Mutex::new(())),
+        }
+    }
+
+    // changes applied through this server from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Change> {
+        self.changes.subscribe()
+    }
+
+    // of the last change applied by this process
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::SeqCst)
+    }
+
+    fn relationship(&self, r: &Relationship) -> Result<EntityRelationship, SchemaError> {
+        let subject = self.schema.entity(&r.subject.namespace, &r.subject.id)?;
+        let role = self.schema.role(&r.object.namespace, &r.role)?;
+        let object = self.schema.entity(&r.object.namespace, &r.object.id)?;
+        Ok(EntityRelationship::new(&subject, &role, &object))
+    }
+
+    // the entity a stored key is of, keys whose id was hashed can't be turned back
+    fn entity_ref(&self, key: &str) -> Result<EntityRef, ApiError> {
+        let entity = self
+            .schema
+            .entity_from_key(key)
+            .ok_or_else(|| ApiError::UnknownKey(key.to_string()))?;
+
+        Ok(EntityRef {
+            namespace: entity.namespace().to_string(),
+            id: entity.id().to_string(),
+        })
+    }
+
+    // Applies the updates in order as one write, the whole batch is validated before and
+    // nothing is written when one fails. Runs as a task of its own, so a client going away
+    // can't leave a written batch without its revisions.
+    async fn apply(&self, updates: Vec<(Operation, Relationship)>) -> Result<Applied, ApiError> {
+        let batch = updates
+            .iter()
+            .map(|(operation, r)| {
+                let r = self.relationship(r)?;
+                Ok(match operation {
+                    Operation::Touch => Update::Touch(r),
+                    Operation::Delete => Update::Delete(r),
+                })
+            })
+            .collect::<Result<Vec<_>, ApiError>>()?;
+
+        let state = self.clone();
+        match tokio::spawn(async move { state.commit(updates, batch).await }).await {
+            Ok(applied) => applied,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(_) => Err(RBACError::Cancelled.into()),
+        }
+    }
+
+    // revisions are taken while the batch holds `writes`, so they follow the order batches
+    // are written in
+    async fn commit(
+        &self,
+        updates: Vec<(Operation, Relationship)>,
+        batch: Vec<Update>,
+    ) -> Result<Applied, ApiError> {
+        let _writes = self.writes.lock().await;
+        let changed = self.rbac.write_batch(batch).await?;
+
+        let mut applied = Applied {
+            count: 0,
+            revision: self.revision(),
+        };
+        for ((operation, relationship), changed) in updates.into_iter().zip(changed) {
+            if changed {
+                applied.count += 1;
+                applied.revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+                // nobody watching is fine
+                let _ = self.changes.send(Change {
+                    revision: applied.revision,
+                    operation,
+                    relationship,
+                });
+            }
+        }
+
+        Ok(applied)
+    }
+}
+
+// outcome of `AppState::apply`
+struct Applied {
+    // updates that changed a relationship
+    count: usize,
+    // of the last change applied, or the one current when nothing changed
+    revision: u64,
+}
+
+// wire types
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EntityRef {
+    pub namespace: String,
+    pub id: String,
+}
+
+// `subject` holds `role` on `object`, the role belongs to the object's namespace
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Relationship {
+    pub subject: EntityRef,
+    pub role: String,
+    pub object: EntityRef,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelationshipsRequest {
+    pub relationships: Vec<Relationship>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WriteResponse {
+    pub written: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteResponse {
+    pub deleted: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckResponse {
+    pub allowed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExplainResponse {
+    pub allowed: bool,
+    // entity keys from the subject to the object, empty when denied
+    pub path: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LookupObjectsRequest {
+    pub subject: EntityRef,
+    pub namespace: String,
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LookupObjectsResponse {
+    pub objects: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LookupSubjectsRequest {
+    pub role: String,
+    pub object: EntityRef,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LookupSubjectsResponse {
+    pub subjects: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operation {
+    Touch,
+    Delete,
+}
+
+// a relationship change applied through this server
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Change {
+    pub revision: u64,
+    pub operation: Operation,
+    pub relationship: Relationship,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    InvalidRequest(String),
+    Schema(SchemaError),
+    Rbac(RBACError),
+    // a stored key that isn't an entity of the schema, or whose id was hashed
+    UnknownKey(String),
+}
+
+impl From<SchemaError> for ApiError {
+    fn from(e: SchemaError) -> Self {
+        ApiError::Schema(e)
+    }
+}
+
+impl From<RBACError> for ApiError {
+    fn from(e: RBACError) -> Self {
+        ApiError::Rbac(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            ApiError::InvalidRequest(e) => (StatusCode::BAD_REQUEST, e),
+            ApiError::Schema(e) => (StatusCode::BAD_REQUEST, format!("{:?}", e)),
+            ApiError::Rbac(RBACError::VertexNotFound) => (
+                StatusCode::NOT_FOUND,
+                format!("{:?}", RBACError::VertexNotFound),
+            ),
+            ApiError::Rbac(e @ RBACError::DepthLimitExceeded(_)) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, format!("{:?}", e))
+            }
+            ApiError::Rbac(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)),
+            ApiError::UnknownKey(key) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("stored key {} doesn't map back to an entity", key),
+            ),
+        };
+
+        (status, Json(ErrorResponse { error })).into_response()
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/v1/relationships/write", post(write_relationships))
+        .route("/v1/relationships/delete", post(delete_relationships))
+        .route("/v1/permissions/check", post(check))
+        .route("/v1/permissions/explain", post(explain))
+        .route("/v1/lookup/objects", post(lookup_objects))
+        .route("/v1/lookup/subjects", post(lookup_subjects))
+        .with_state(state)
+}
+
+pub async fn serve(listener: TcpListener, state: AppState) -> std::io::Result<()> {
+    axum::serve(listener, router(state)).await
+}
+
+// handlers
+
+async fn write_relationships(
+    State(state): State<AppState>,
+    Json(req): Json<RelationshipsRequest>,
+) -> Result<Json<WriteResponse>, ApiError> {
+    let updates = req
+        .relationships
+        .into_iter()
+        .map(|r| (Operation::Touch, r))
+        .collect();
+    let written = state.apply(updates).await?.count;
+
+    Ok(Json(WriteResponse { written }))
+}
+
+async fn delete_relationships(
+    State(state): State<AppState>,
+    Json(req): Json<RelationshipsRequest>,
+) -> Result<Json<DeleteResponse>, ApiError> {
+    let updates = req
+        .relationships
+        .into_iter()
+        .map(|r| (Operation::Delete, r))
+        .collect();
+    let deleted = state.apply(updates).await?.count;
+
+    Ok(Json(DeleteResponse { deleted }))
+}
+
+// subjects or objects the store has never seen simply have no grants, so they're denied
+async fn check(
+    State(state): State<AppState>,
+    Json(req): Json<Relationship>,
+) -> Result<Json<CheckResponse>, ApiError> {
+    let allowed = match state.rbac.allowed(state.relationship(&req)?).await {
+        Ok(allowed) => allowed,
+        Err(RBACError::VertexNotFound) => false,
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(Json(CheckResponse { allowed }))
+}
+
+async fn explain(
+    State(state): State<AppState>,
+    Json(req): Json<Relationship>,
+) -> Result<Json<ExplainResponse>, ApiError> {
+    let path = match state.rbac.explain(state.relationship(&req)?).await {
+        Ok(path) => path,
+        Err(RBACError::VertexNotFound) => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(Json(ExplainResponse {
+        allowed: path.is_some(),
+        path: path.unwrap_or_default(),
+    }))
+}
+
+async fn lookup_objects(
+    State(state): State<AppState>,
+    Json(req): Json<LookupObjectsRequest>,
+) -> Result<Json<LookupObjectsResponse>, ApiError> {
+    let subject = state
+        .schema
+        .entity(&req.subject.namespace, &req.subject.id)?;
+    let role = state.schema.role(&req.namespace, &req.role)?;
+
+    let objects = state.rbac.lookup_objects(subject, role).await?;
+
+    Ok(Json(LookupObjectsResponse { objects }))
+}
+
+async fn lookup_subjects(
+    State(state): State<AppState>,
+    Json(req): Json<LookupSubjectsRequest>,
+) -> Result<Json<LookupSubjectsResponse>, ApiError> {
+    let role = state.schema.role(&req.object.namespace, &req.role)?;
+    let object = state.schema.entity(&req.object.namespace, &req.object.id)?;
+
+    let subjects = state.rbac.lookup_subjects(role, object).await?;
+
+    Ok(Json(LookupSubjectsResponse { subjects }))
+}
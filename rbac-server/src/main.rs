@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use rbac::{schema::Schema, RBAC};
+use rbac_server::AppState;
+use tokio::net::TcpListener;
+
+/// JSON/HTTP authorization server, meant to run as a sidecar next to the services using it
+#[derive(Parser)]
+struct Args {
+    /// path to the rocksdb datastore
+    #[arg(long)]
+    db: String,
+
+    /// json schema describing namespaces and roles
+    #[arg(long)]
+    schema: PathBuf,
+
+    /// address to serve the JSON/HTTP api on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+
+    /// also serve the gRPC api on this address when set
+    #[arg(long)]
+    grpc_addr: Option<String>,
+
+    /// how deep checks and lookups traverse the graph
+    #[arg(long, default_value_t = rbac::DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    let schema = Schema::load(&args.schema).unwrap();
+    let rbac = RBAC::new(&args.db).with_max_depth(args.max_depth);
+
+    let state = AppState::new(rbac, schema);
+
+    if let Some(grpc_addr) = &args.grpc_addr {
+        let listener = TcpListener::bind(grpc_addr).await.unwrap();
+        println!("grpc listening on {}", listener.local_addr().unwrap());
+
+        let state = state.clone();
+        tokio::spawn(async move { rbac_server::grpc::serve(listener, state).await.unwrap() });
+    }
+
+    let listener = TcpListener::bind(&args.addr).await.unwrap();
+    println!("listening on {}", listener.local_addr().unwrap());
+
+    rbac_server::serve(listener, state).await.unwrap();
+}
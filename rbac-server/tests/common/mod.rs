@@ -0,0 +1,12 @@
+use rbac::{schema::Schema, RBAC};
+use rbac_server::AppState;
+use tempfile::TempDir;
+
+// server state over an empty store in a directory of its own, removed with the `TempDir`
+pub fn state() -> (AppState, TempDir) {
+    let store = tempfile::tempdir().unwrap();
+    let rbac = RBAC::new(store.path().to_str().unwrap());
+    let schema = Schema::from_json(include_str!("../../community.json")).unwrap();
+
+    (AppState::new(rbac, schema), store)
+}
@@ -0,0 +1,365 @@
+use rbac_grpc_client::{
+    v1::{self, check_permission_response::Permissionship, relationship_update::Operation},
+    PermissionsServiceClient, WatchServiceClient,
+};
+use rbac_server::grpc::serve;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+
+mod common;
+
+// address of a server over a store of its own, which lasts as long as the `TempDir`
+async fn spawn() -> (String, TempDir) {
+    let (state, store) = common::state();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, state));
+
+    (format!("http://{}", addr), store)
+}
+
+fn object(object_type: &str, object_id: &str) -> Option<v1::ObjectReference> {
+    Some(v1::ObjectReference {
+        object_type: object_type.into(),
+        object_id: object_id.into(),
+    })
+}
+
+fn subject(object_type: &str, object_id: &str) -> Option<v1::SubjectReference> {
+    Some(v1::SubjectReference {
+        object: object(object_type, object_id),
+    })
+}
+
+fn touch(resource: (&str, &str), relation: &str, by: (&str, &str)) -> v1::RelationshipUpdate {
+    v1::RelationshipUpdate {
+        operation: Operation::Touch.into(),
+        relationship: Some(v1::Relationship {
+            resource: object(resource.0, resource.1),
+            relation: relation.into(),
+            subject: subject(by.0, by.1),
+        }),
+    }
+}
+
+#[tokio::test]
+async fn grpc_check_and_lookup() {
+    let (base, _store) = spawn().await;
+    let mut client = PermissionsServiceClient::connect(base).await.unwrap();
+
+    client
+        .write_relationships(v1::WriteRelationshipsRequest {
+            updates: vec![
+                touch(("Post", "1"), "Writer", ("User", "1")),
+                touch(("Group", "1"), "Member", ("User", "2")),
+                touch(("Post", "1"), "Viewer", ("Group", "1")),
+            ],
+        })
+        .await
+        .unwrap();
+
+    let res = client
+        .check_permission(v1::CheckPermissionRequest {
+            resource: object("Post", "1"),
+            permission: "Viewer".into(),
+            subject: subject("User", "2"),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.permissionship(), Permissionship::HasPermission);
+
+    let res = client
+        .check_permission(v1::CheckPermissionRequest {
+            resource: object("Post", "1"),
+            permission: "Writer".into(),
+            subject: subject("User", "2"),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.permissionship(), Permissionship::NoPermission);
+
+    let mut stream = client
+        .lookup_resources(v1::LookupResourcesRequest {
+            resource_object_type: "Post".into(),
+            permission: "Viewer".into(),
+            subject: subject("User", "1"),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let mut resources = Vec::new();
+    while let Some(res) = stream.message().await.unwrap() {
+        resources.push(res.resource_object_id);
+    }
+    assert_eq!(resources, vec!["1"]);
+
+    let mut stream = client
+        .lookup_subjects(v1::LookupSubjectsRequest {
+            resource: object("Post", "1"),
+            permission: "Viewer".into(),
+            subject_object_type: "User".into(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let mut subjects = Vec::new();
+    while let Some(res) = stream.message().await.unwrap() {
+        subjects.push(res.subject.unwrap().object.unwrap().object_id);
+    }
+    assert_eq!(subjects, vec!["1", "2"]);
+
+    // unknown roles are rejected
+    let status = client
+        .check_permission(v1::CheckPermissionRequest {
+            resource: object("Post", "1"),
+            permission: "Member".into(),
+            subject: subject("User", "2"),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn grpc_write_in_order() {
+    let (base, _store) = spawn().await;
+    let mut client = PermissionsServiceClient::connect(base).await.unwrap();
+
+    let mut delete = touch(("Post", "1"), "Writer", ("User", "1"));
+    delete.operation = Operation::Delete.into();
+
+    // the same relationship twice is rejected before anything is written
+    let status = client
+        .write_relationships(v1::WriteRelationshipsRequest {
+            updates: vec![
+                touch(("Group", "1"), "Member", ("User", "1")),
+                touch(("Post", "1"), "Writer", ("User", "1")),
+                delete.clone(),
+            ],
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    let res = client
+        .write_relationships(v1::WriteRelationshipsRequest {
+            updates: vec![touch(("Post", "1"), "Writer", ("User", "1"))],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.revision, 1);
+
+    // a delete before a touch is applied first
+    let res = client
+        .write_relationships(v1::WriteRelationshipsRequest {
+            updates: vec![delete, touch(("Post", "1"), "Viewer", ("User", "1"))],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.revision, 3);
+
+    let res = client
+        .check_permission(v1::CheckPermissionRequest {
+            resource: object("Post", "1"),
+            permission: "Writer".into(),
+            subject: subject("User", "1"),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.permissionship(), Permissionship::NoPermission);
+
+    // nothing changed, the revision stays
+    let res = client
+        .write_relationships(v1::WriteRelationshipsRequest {
+            updates: vec![touch(("Post", "1"), "Viewer", ("User", "1"))],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.revision, 3);
+}
+
+#[tokio::test]
+async fn grpc_watch() {
+    let (base, _store) = spawn().await;
+    let mut watch = WatchServiceClient::connect(base.clone()).await.unwrap();
+    let mut client = PermissionsServiceClient::connect(base).await.unwrap();
+
+    let mut changes = watch
+        .watch(v1::WatchRequest {
+            object_types: vec!["Post".into()],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    client
+        .write_relationships(v1::WriteRelationshipsRequest {
+            updates: vec![
+                touch(("Group", "1"), "Member", ("User", "1")),
+                touch(("Post", "1"), "Writer", ("User", "1")),
+            ],
+        })
+        .await
+        .unwrap();
+
+    let res = client
+        .delete_relationships(v1::DeleteRelationshipsRequest {
+            relationships: vec![touch(("Post", "1"), "Writer", ("User", "1"))
+                .relationship
+                .unwrap()],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.deleted, 1);
+    assert_eq!(res.revision, 3);
+
+    // the group write is filtered out
+    let change = changes.message().await.unwrap().unwrap();
+    assert_eq!(change.revision, 2);
+    assert_eq!(change.updates[0].operation(), Operation::Touch);
+
+    let change = changes.message().await.unwrap().unwrap();
+    assert_eq!(change.revision, 3);
+    assert_eq!(change.updates[0].operation(), Operation::Delete);
+    let relationship = change.updates[0].relationship.as_ref().unwrap();
+    assert_eq!(relationship.relation, "Writer");
+    assert_eq!(relationship.resource, object("Post", "1"));
+}
+
+#[tokio::test]
+async fn grpc_write_is_all_or_nothing() {
+    let (base, _store) = spawn().await;
+    let mut client = PermissionsServiceClient::connect(base).await.unwrap();
+
+    // the unknown role fails the batch, the writer before it isn't written either
+    let status = client
+        .write_relationships(v1::WriteRelationshipsRequest {
+            updates: vec![
+                touch(("Post", "1"), "Writer", ("User", "1")),
+                touch(("Post", "1"), "Member", ("User", "1")),
+            ],
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    let res = client
+        .check_permission(v1::CheckPermissionRequest {
+            resource: object("Post", "1"),
+            permission: "Writer".into(),
+            subject: subject("User", "1"),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.permissionship(), Permissionship::NoPermission);
+
+    let res = client
+        .write_relationships(v1::WriteRelationshipsRequest {
+            updates: vec![touch(("Post", "1"), "Viewer", ("User", "1"))],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.revision, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn grpc_revisions_follow_writes() {
+    let (base, _store) = spawn().await;
+    let mut watch = WatchServiceClient::connect(base.clone()).await.unwrap();
+    let client = PermissionsServiceClient::connect(base).await.unwrap();
+
+    let mut changes = watch
+        .watch(v1::WatchRequest {
+            object_types: Vec::new(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let writers: Vec<_> = (0..16)
+        .map(|i| {
+            let mut client = client.clone();
+            tokio::spawn(async move {
+                let user = i.to_string();
+                let res = client
+                    .write_relationships(v1::WriteRelationshipsRequest {
+                        updates: vec![touch(("Post", "1"), "Viewer", ("User", &user))],
+                    })
+                    .await
+                    .unwrap()
+                    .into_inner();
+                (res.revision, user)
+            })
+        })
+        .collect();
+
+    let mut written = Vec::new();
+    for writer in writers {
+        written.push(writer.await.unwrap());
+    }
+    written.sort();
+    assert_eq!(
+        written
+            .iter()
+            .map(|(revision, _)| *revision)
+            .collect::<Vec<_>>(),
+        (1..=16).collect::<Vec<u64>>()
+    );
+
+    // watchers see the changes in the order of their revisions, each as its write reported it
+    for (revision, user) in written {
+        let change = changes.message().await.unwrap().unwrap();
+        assert_eq!(change.revision, revision);
+        let relationship = change.updates[0].relationship.as_ref().unwrap();
+        assert_eq!(relationship.subject, subject("User", &user));
+    }
+}
+
+#[tokio::test]
+async fn grpc_lookup_hashed_ids() {
+    let (base, _store) = spawn().await;
+    let mut client = PermissionsServiceClient::connect(base).await.unwrap();
+
+    // ids this long are stored hashed and can't be read back from the key
+    let long = "a".repeat(100);
+    client
+        .write_relationships(v1::WriteRelationshipsRequest {
+            updates: vec![
+                touch(("Post", &long), "Viewer", ("User", "1")),
+                touch(("Post", "1"), "Viewer", ("User", &long)),
+            ],
+        })
+        .await
+        .unwrap();
+
+    // a lookup fails rather than leaving them out
+    let status = client
+        .lookup_resources(v1::LookupResourcesRequest {
+            resource_object_type: "Post".into(),
+            permission: "Viewer".into(),
+            subject: subject("User", "1"),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Internal);
+
+    let status = client
+        .lookup_subjects(v1::LookupSubjectsRequest {
+            resource: object("Post", "1"),
+            permission: "Viewer".into(),
+            subject_object_type: String::new(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Internal);
+}
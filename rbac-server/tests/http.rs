@@ -0,0 +1,183 @@
+use rbac_server::{
+    serve, CheckResponse, DeleteResponse, ExplainResponse, LookupObjectsResponse,
+    LookupSubjectsResponse, WriteResponse,
+};
+use serde_json::json;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+
+mod common;
+
+// base url of a server over a store of its own, which lasts as long as the `TempDir`
+async fn spawn() -> (String, TempDir) {
+    let (state, store) = common::state();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, state));
+
+    (format!("http://{}", addr), store)
+}
+
+fn relationship(subject: (&str, &str), role: &str, object: (&str, &str)) -> serde_json::Value {
+    json!({
+        "subject": { "namespace": subject.0, "id": subject.1 },
+        "role": role,
+        "object": { "namespace": object.0, "id": object.1 },
+    })
+}
+
+#[tokio::test]
+async fn http_check_and_lookup() {
+    let (base, _store) = spawn().await;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .post(format!("{}/v1/relationships/write", base))
+        .json(&json!({
+            "relationships": [
+                relationship(("User", "1"), "Writer", ("Post", "1")),
+                relationship(("User", "2"), "Member", ("Group", "1")),
+                relationship(("Group", "1"), "Viewer", ("Post", "1")),
+            ]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let res: WriteResponse = res.json().await.unwrap();
+    assert_eq!(res.written, 3);
+
+    let res: CheckResponse = client
+        .post(format!("{}/v1/permissions/check", base))
+        .json(&relationship(("User", "2"), "Viewer", ("Post", "1")))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res.allowed, true);
+
+    // never seen subjects are denied
+    let res: CheckResponse = client
+        .post(format!("{}/v1/permissions/check", base))
+        .json(&relationship(("User", "3"), "Viewer", ("Post", "1")))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res.allowed, false);
+
+    let res: ExplainResponse = client
+        .post(format!("{}/v1/permissions/explain", base))
+        .json(&relationship(("User", "2"), "Viewer", ("Post", "1")))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res.allowed, true);
+    assert_eq!(
+        res.path,
+        vec![
+            "User_2",
+            "Group_1_Member",
+            "Group_1",
+            "Post_1_Viewer",
+            "Post_1"
+        ]
+    );
+
+    let res: LookupObjectsResponse = client
+        .post(format!("{}/v1/lookup/objects", base))
+        .json(&json!({
+            "subject": { "namespace": "User", "id": "2" },
+            "namespace": "Post",
+            "role": "Viewer",
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res.objects, vec!["Post_1"]);
+
+    let res: LookupSubjectsResponse = client
+        .post(format!("{}/v1/lookup/subjects", base))
+        .json(&json!({
+            "role": "Viewer",
+            "object": { "namespace": "Post", "id": "1" },
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res.subjects, vec!["Group_1", "User_1", "User_2"]);
+}
+
+#[tokio::test]
+async fn http_delete_and_errors() {
+    let (base, _store) = spawn().await;
+    let client = reqwest::Client::new();
+
+    let body = json!({
+        "relationships": [relationship(("User", "1"), "Viewer", ("Post", "1"))]
+    });
+
+    client
+        .post(format!("{}/v1/relationships/write", base))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+
+    let res: DeleteResponse = client
+        .post(format!("{}/v1/relationships/delete", base))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res.deleted, 1);
+
+    let res: CheckResponse = client
+        .post(format!("{}/v1/permissions/check", base))
+        .json(&relationship(("User", "1"), "Viewer", ("Post", "1")))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res.allowed, false);
+
+    // roles are resolved against the object's namespace
+    let res = client
+        .post(format!("{}/v1/permissions/check", base))
+        .json(&relationship(("User", "1"), "Member", ("Post", "1")))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 400);
+
+    let res = client
+        .post(format!("{}/v1/lookup/objects", base))
+        .json(&json!({
+            "subject": { "namespace": "User", "id": "42" },
+            "namespace": "Post",
+            "role": "Viewer",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+}
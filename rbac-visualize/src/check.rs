@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+
+use egui::Ui;
+use rbac::{schema::Schema, EntityRelationship, RBACError, RBAC};
+
+use crate::{node::HIGHLIGHT, ViewGraph};
+
+// "check" panel, e.g. subject `User:1`, role `Viewer`, object `Post:1`
+#[derive(Default)]
+pub struct CheckPanel {
+    subject: String,
+    role: String,
+    object: String,
+    result: Option<CheckResult>,
+}
+
+enum CheckResult {
+    // entity keys from the subject to the object
+    Allowed(Vec<String>),
+    Denied,
+    Error(String),
+}
+
+impl CheckPanel {
+    // entity keys to highlight, empty unless the last check was granted
+    pub fn path(&self) -> &[String] {
+        match &self.result {
+            Some(CheckResult::Allowed(path)) => path,
+            _ => &[],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.result = None;
+    }
+
+    // returns true when a check ran and the highlighted path may have changed
+    pub fn show(&mut self, ui: &mut Ui, rbac: &RBAC, schema: Option<&Schema>) -> bool {
+        ui.heading("Check");
+
+        let schema = match schema {
+            Some(schema) => schema,
+            None => {
+                ui.label("open the database with a schema to run checks");
+                return false;
+            }
+        };
+
+        ui.label("subject");
+        ui.text_edit_singleline(&mut self.subject);
+        ui.label("role");
+        ui.text_edit_singleline(&mut self.role);
+        ui.label("object");
+        ui.text_edit_singleline(&mut self.object);
+
+        let checked = ui.button("Check").clicked();
+        if checked {
+            self.result = Some(self.run(rbac, schema));
+        }
+
+        match &self.result {
+            Some(CheckResult::Allowed(path)) => {
+                ui.colored_label(HIGHLIGHT, "allowed");
+                for key in path {
+                    ui.monospace(key);
+                }
+            }
+            Some(CheckResult::Denied) => {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "denied, no path grants the role",
+                );
+            }
+            Some(CheckResult::Error(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+            None => {}
+        }
+
+        checked
+    }
+
+    fn run(&self, rbac: &RBAC, schema: &Schema) -> CheckResult {
+        let tuple = format!(
+            "{}#{}@{}",
+            self.object.trim(),
+            self.role.trim(),
+            self.subject.trim()
+        );
+        let (subject, role, object) = match schema.parse_relationship(&tuple) {
+            Ok(parsed) => parsed,
+            Err(e) => return CheckResult::Error(format!("{:?}", e)),
+        };
+
+        match rbac.explain(&EntityRelationship::new(&subject, &role, &object)) {
+            Ok(Some(path)) => CheckResult::Allowed(path),
+            // subjects or objects the store has never seen have no grants
+            Ok(None) | Err(RBACError::VertexNotFound) => CheckResult::Denied,
+            Err(e) => CheckResult::Error(format!("{:?}", e)),
+        }
+    }
+}
+
+// marks the vertices of `path` and the edges between consecutive ones, unmarking the rest
+pub fn highlight_path(g: &mut ViewGraph, path: &[String]) {
+    let keys: HashSet<&str> = path.iter().map(|key| key.as_str()).collect();
+    let steps: HashSet<(&str, &str)> = path
+        .windows(2)
+        .map(|step| (step[0].as_str(), step[1].as_str()))
+        .collect();
+
+    let nodes: Vec<_> = g.nodes_iter().map(|(idx, _)| idx).collect();
+    for idx in nodes {
+        if let Some(n) = g.node_mut(idx) {
+            let highlighted = keys.contains(n.payload().entity.as_str());
+            n.payload_mut().highlighted = highlighted;
+        }
+    }
+
+    let edges: Vec<_> = g
+        .edges_iter()
+        .filter_map(|(e, _)| {
+            let (a, b) = g.edge_endpoints(e)?;
+            let a = g.node(a)?.payload().entity.as_str();
+            let b = g.node(b)?.payload().entity.as_str();
+            Some((e, steps.contains(&(a, b))))
+        })
+        .collect();
+    for (e, highlighted) in edges {
+        if let Some(edge) = g.edge_mut(e) {
+            edge.payload_mut().highlighted = highlighted;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui_graphs::Graph;
+
+    use super::*;
+    use crate::fixture::{self, find_shown};
+
+    fn highlighted(g: &ViewGraph) -> (Vec<String>, usize) {
+        let mut nodes: Vec<String> = g
+            .nodes_iter()
+            .filter(|(_, n)| n.payload().highlighted)
+            .map(|(_, n)| n.payload().entity.clone())
+            .collect();
+        nodes.sort();
+        let edges = g
+            .edges_iter()
+            .filter(|(_, e)| e.payload().highlighted)
+            .count();
+        (nodes, edges)
+    }
+
+    #[test]
+    fn highlight_path_marks_its_steps() {
+        let mut g: ViewGraph = Graph::from(&fixture::graph());
+        let path: Vec<String> = [
+            "User_alice",
+            "Group_1_Member",
+            "Group_1",
+            "Post_1_Viewer",
+            "Post_1",
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+        highlight_path(&mut g, &path);
+        let (nodes, edges) = highlighted(&g);
+        let mut expected = path.clone();
+        expected.sort();
+        assert_eq!(nodes, expected);
+        assert_eq!(edges, 4);
+
+        // the writer's edges touch the path without being a step of it
+        let writer = find_shown(&g, "Post_1_Writer").unwrap();
+        assert!(!g.node(writer).unwrap().payload().highlighted);
+        assert!(g
+            .edges_iter()
+            .filter(|(e, _)| g.edge_endpoints(*e).is_some_and(|(a, _)| a == writer))
+            .all(|(_, e)| !e.payload().highlighted));
+
+        // an empty path clears it
+        highlight_path(&mut g, &[]);
+        assert_eq!(highlighted(&g), (Vec::new(), 0));
+    }
+}
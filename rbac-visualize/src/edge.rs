@@ -0,0 +1,100 @@
+use egui::{epaint::ColorMode, Color32, Pos2, Shape};
+use egui_graphs::{DefaultEdgeShape, DisplayEdge, DisplayNode, DrawContext, EdgeProps, Node};
+use petgraph::{stable_graph::IndexType, EdgeType};
+
+use crate::{
+    node::HIGHLIGHT,
+    style::{edge_color, edge_width},
+};
+
+#[derive(Clone, Debug)]
+pub struct EdgeData {
+    // `entity_to_role`, `inherits` or `role_to_entity`
+    pub t: String,
+    // on the path of the last check
+    pub highlighted: bool,
+}
+
+// egui_graphs' default edge in the color of its type, or in `HIGHLIGHT` when it's on the path
+// of a check
+#[derive(Clone)]
+pub struct EdgeShapeFlex {
+    default: DefaultEdgeShape,
+    t: String,
+    highlighted: bool,
+}
+
+impl From<EdgeProps<EdgeData>> for EdgeShapeFlex {
+    fn from(edge_props: EdgeProps<EdgeData>) -> Self {
+        let mut shape = Self {
+            t: edge_props.payload.t.clone(),
+            highlighted: edge_props.payload.highlighted,
+            default: DefaultEdgeShape::from(edge_props),
+        };
+        shape.default.width = shape.width();
+        shape
+    }
+}
+
+impl EdgeShapeFlex {
+    fn width(&self) -> f32 {
+        if self.highlighted {
+            3.
+        } else {
+            edge_width(&self.t)
+        }
+    }
+}
+
+impl<N: Clone, Ty: EdgeType, Ix: IndexType, D: DisplayNode<N, EdgeData, Ty, Ix>>
+    DisplayEdge<N, EdgeData, Ty, Ix, D> for EdgeShapeFlex
+{
+    fn shapes(
+        &mut self,
+        start: &Node<N, EdgeData, Ty, Ix, D>,
+        end: &Node<N, EdgeData, Ty, Ix, D>,
+        ctx: &DrawContext,
+    ) -> Vec<Shape> {
+        let mut shapes =
+            DisplayEdge::<N, EdgeData, Ty, Ix, D>::shapes(&mut self.default, start, end, ctx);
+
+        let color = match (self.highlighted, self.default.selected) {
+            (true, _) => HIGHLIGHT,
+            // selected edges keep egui_graphs' selection style
+            (false, true) => return shapes,
+            (false, false) => edge_color(&self.t),
+        };
+        for shape in &mut shapes {
+            tint(shape, color);
+        }
+
+        shapes
+    }
+
+    fn update(&mut self, state: &EdgeProps<EdgeData>) {
+        DisplayEdge::<N, EdgeData, Ty, Ix, D>::update(&mut self.default, state);
+        self.t = state.payload.t.clone();
+        self.highlighted = state.payload.highlighted;
+        self.default.width = self.width();
+    }
+
+    fn is_inside(
+        &self,
+        start: &Node<N, EdgeData, Ty, Ix, D>,
+        end: &Node<N, EdgeData, Ty, Ix, D>,
+        pos: Pos2,
+    ) -> bool {
+        DisplayEdge::<N, EdgeData, Ty, Ix, D>::is_inside(&self.default, start, end, pos)
+    }
+}
+
+// recolors the lines, curves, arrow tips and labels the default edge is drawn with
+fn tint(shape: &mut Shape, color: Color32) {
+    match shape {
+        Shape::LineSegment { stroke, .. } => stroke.color = ColorMode::Solid(color),
+        Shape::CubicBezier(curve) => curve.stroke.color = ColorMode::Solid(color),
+        Shape::Path(path) => path.fill = color,
+        Shape::Text(text) => text.override_text_color = Some(color),
+        _ => {}
+    }
+}
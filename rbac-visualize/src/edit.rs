@@ -0,0 +1,358 @@
+use std::collections::BTreeSet;
+
+use egui::{ComboBox, Context, Ui, Window};
+use petgraph::stable_graph::{EdgeIndex, StableGraph};
+use rbac::{
+    decode_key_part,
+    schema::{Schema, SchemaEntity, SchemaRole},
+    EntityRelationship, RBACError, WriteMode, ENTITY_TO_ROLE,
+};
+
+use crate::{
+    edge::EdgeData,
+    node::{NodeData, NodeKind},
+    store::Store,
+    ViewGraph,
+};
+
+// "edit" panel granting roles, revoking them is offered in the inspector. Writes go through
+// `RBAC`, so the role hierarchy of a new object is materialized as for any other client.
+#[derive(Default)]
+pub struct EditPanel {
+    // entity keys
+    subject: Option<String>,
+    object: Option<String>,
+    role: Option<String>,
+    // grant to revoke, waiting for confirmation
+    revoking: Option<Revoke>,
+    // outcome of the last write
+    result: Option<Result<String, String>>,
+}
+
+struct Revoke {
+    subject: SchemaEntity,
+    role: SchemaRole,
+    object: SchemaEntity,
+}
+
+impl EditPanel {
+    pub fn clear(&mut self) {
+        *self = EditPanel::default();
+    }
+
+    // returns true when a write was tried, the graph may have changed
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        store: &Store,
+        full: &StableGraph<NodeData, EdgeData>,
+    ) -> bool {
+        ui.heading("Edit");
+
+        let schema = match editable(store) {
+            Ok(schema) => schema,
+            Err(reason) => {
+                ui.label(reason);
+                return false;
+            }
+        };
+
+        // every entity can be a subject, objects need a namespace with roles
+        let entities: BTreeSet<&str> = full
+            .node_weights()
+            .filter(|n| n.kind != NodeKind::Role)
+            .map(|n| n.entity.as_str())
+            .collect();
+        let objects: Vec<&str> = entities
+            .iter()
+            .copied()
+            .filter(|key| !roles_of(schema, key).is_empty())
+            .collect();
+
+        ui.label("subject");
+        choose(
+            ui,
+            "subject",
+            &mut self.subject,
+            entities.iter().copied(),
+            schema,
+        );
+        ui.label("object");
+        let object = self.object.clone();
+        choose(ui, "object", &mut self.object, objects.into_iter(), schema);
+        // roles of another namespace don't apply
+        if self.object != object {
+            self.role = None;
+        }
+        ui.label("role");
+        let roles = self
+            .object
+            .as_deref()
+            .map(|key| roles_of(schema, key))
+            .unwrap_or_default();
+        ComboBox::from_id_salt("role")
+            .selected_text(self.role.as_deref().unwrap_or(""))
+            .show_ui(ui, |ui| {
+                for role in roles {
+                    ui.selectable_value(&mut self.role, Some(role.clone()), role);
+                }
+            });
+
+        let ready = self.subject.is_some() && self.object.is_some() && self.role.is_some();
+        let mut wrote = false;
+        if ui.add_enabled(ready, egui::Button::new("Grant")).clicked() {
+            self.result = Some(self.grant(store, schema));
+            wrote = true;
+        }
+
+        match &self.result {
+            Some(Ok(done)) => {
+                ui.label(done);
+            }
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+            None => {}
+        }
+
+        wrote
+    }
+
+    fn grant(&self, store: &Store, schema: &Schema) -> Result<String, String> {
+        let (subject, object, role) = match (&self.subject, &self.object, &self.role) {
+            (Some(subject), Some(object), Some(role)) => (subject, object, role),
+            _ => return Err("choose a subject, an object and a role".to_string()),
+        };
+        let subject = entity(schema, subject)?;
+        let object = entity(schema, object)?;
+        let role = schema
+            .role(object.namespace(), role)
+            .map_err(|e| format!("{:?}", e))?;
+
+        let relationship = EntityRelationship::new(&subject, &role, &object);
+        match store
+            .rbac
+            .write_relationship(&relationship, WriteMode::Create, &[])
+        {
+            Ok(_) => Ok(format!("granted {}#{}@{}", object, role.name(), subject)),
+            Err(RBACError::RelationshipExists) => Err(format!(
+                "{}#{}@{} is already granted",
+                object,
+                role.name(),
+                subject
+            )),
+            Err(e) => Err(format!("{:?}", e)),
+        }
+    }
+
+    // offers to revoke the grant `edge` stands for, shown in the inspector
+    pub fn show_edge(
+        &mut self,
+        ui: &mut Ui,
+        g: &ViewGraph,
+        store: &Store,
+        edge: Option<EdgeIndex>,
+    ) {
+        let t = match edge.and_then(|e| g.edge(e)) {
+            Some(edge) => edge.payload().t.as_str(),
+            None => return,
+        };
+        let schema = match editable(store) {
+            Ok(schema) => schema,
+            // nothing to offer when the store can't be edited anyway
+            Err(_) => return,
+        };
+
+        ui.separator();
+        if t != ENTITY_TO_ROLE {
+            ui.label("maintained by the role hierarchy, revoke the grants instead");
+            return;
+        }
+
+        match edge.and_then(|e| grant_of(g, schema, e)) {
+            Some(revoke) => {
+                if ui.button("Revoke…").clicked() {
+                    self.revoking = Some(revoke);
+                }
+            }
+            None => {
+                ui.label("doesn't fit the schema, can't be revoked here");
+            }
+        }
+    }
+
+    // asks before revoking, returns true when a write was tried
+    pub fn confirm(&mut self, ctx: &Context, store: &Store) -> bool {
+        let revoke = match &self.revoking {
+            Some(revoke) => revoke,
+            None => return false,
+        };
+        let tuple = format!(
+            "{}#{}@{}",
+            revoke.object,
+            revoke.role.name(),
+            revoke.subject
+        );
+
+        let (mut confirmed, mut cancelled) = (false, false);
+        Window::new("Revoke role")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("revoke {}?", tuple));
+                ui.label("roles held through it are lost too");
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("Revoke").clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+
+        if confirmed {
+            let relationship =
+                EntityRelationship::new(&revoke.subject, &revoke.role, &revoke.object);
+            self.result = Some(match store.rbac.remove_relationship(&relationship) {
+                Ok(true) => Ok(format!("revoked {}", tuple)),
+                Ok(false) => Err(format!("{} was already revoked", tuple)),
+                Err(e) => Err(format!("{:?}", e)),
+            });
+        }
+        if confirmed || cancelled {
+            self.revoking = None;
+        }
+
+        confirmed
+    }
+}
+
+// the schema to write with, or why the store can't be edited
+fn editable(store: &Store) -> Result<&Schema, &'static str> {
+    if !store.writable {
+        return Err("open the database for editing to change it");
+    }
+    store
+        .schema
+        .as_ref()
+        .ok_or("open the database with a schema to change it")
+}
+
+fn entity(schema: &Schema, key: &str) -> Result<SchemaEntity, String> {
+    schema
+        .entity_from_key(key)
+        .ok_or_else(|| format!("{} doesn't fit the schema", key))
+}
+
+// roles defined on the namespace of the entity keyed `key`
+fn roles_of(schema: &Schema, key: &str) -> Vec<String> {
+    schema
+        .entity_from_key(key)
+        .and_then(|entity| schema.namespace(entity.namespace()).ok())
+        .map(|namespace| {
+            namespace
+                .roles
+                .iter()
+                .map(|role| role.name.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// a combo box of entity keys, shown as `Namespace:id`
+fn choose<'a>(
+    ui: &mut Ui,
+    id: &str,
+    chosen: &mut Option<String>,
+    keys: impl Iterator<Item = &'a str>,
+    schema: &Schema,
+) {
+    let label = |key: &str| {
+        schema
+            .entity_from_key(key)
+            .map_or(key.to_string(), |entity| entity.to_string())
+    };
+
+    ComboBox::from_id_salt(id)
+        .selected_text(chosen.as_deref().map(label).unwrap_or_default())
+        .show_ui(ui, |ui| {
+            for key in keys {
+                ui.selectable_value(chosen, Some(key.to_string()), label(key));
+            }
+        });
+}
+
+// the grant an `entity_to_role` edge was written for
+fn grant_of(g: &ViewGraph, schema: &Schema, e: EdgeIndex) -> Option<Revoke> {
+    let (subject_idx, role_idx) = g.edge_endpoints(e)?;
+    let subject_key = &g.node(subject_idx)?.payload().entity;
+    let role_key = &g.node(role_idx)?.payload().entity;
+
+    // role vertices are keyed `{object key}_{Role}`, the object may be filtered out of the view
+    let (object_key, role) = role_key.rsplit_once('_')?;
+    let role = decode_key_part(role)?;
+
+    let object = schema.entity_from_key(object_key)?;
+    Some(Revoke {
+        subject: schema.entity_from_key(subject_key)?,
+        role: schema.role(object.namespace(), &role).ok()?,
+        object,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use egui_graphs::Graph;
+
+    use super::*;
+    use crate::fixture::{self, edge, find, node};
+
+    // the grant the `entity_to_role` edge from `subject` to `role` stands for
+    fn grant(full: &StableGraph<NodeData, EdgeData>, subject: &str, role: &str) -> Option<Revoke> {
+        let g: ViewGraph = Graph::from(full);
+        let e = g
+            .edges_iter()
+            .map(|(e, _)| e)
+            .find(|e| {
+                let (a, b) = g.edge_endpoints(*e).unwrap();
+                g.node(a).unwrap().payload().entity == subject
+                    && g.node(b).unwrap().payload().entity == role
+            })
+            .unwrap();
+        grant_of(&g, &fixture::schema(), e)
+    }
+
+    #[test]
+    fn grant_of_role_key() {
+        let mut full = fixture::graph();
+        for (subject, kind, role) in [
+            ("User_alice", NodeKind::Entity, "Post_1_Editor"),
+            ("User_alice", NodeKind::Entity, "Team_1_Member"),
+            ("Bot_1", NodeKind::Entity, "Post_1_Viewer"),
+        ] {
+            for (entity, kind) in [(subject, kind), (role, NodeKind::Role)] {
+                if full.node_weights().all(|n| n.entity != entity) {
+                    full.add_node(node(entity, kind));
+                }
+            }
+            let (a, b) = (find(&full, subject), find(&full, role));
+            full.add_edge(a, b, edge(ENTITY_TO_ROLE));
+        }
+
+        let revoke = grant(&full, "User_alice", "Group_1_Member").unwrap();
+        assert_eq!(revoke.subject.to_string(), "User:alice");
+        assert_eq!(
+            (revoke.role.namespace(), revoke.role.name()),
+            ("Group", "Member")
+        );
+        assert_eq!(revoke.object.to_string(), "Group:1");
+
+        // groups hold roles too
+        let revoke = grant(&full, "Group_1", "Post_1_Viewer").unwrap();
+        assert_eq!(revoke.subject.to_string(), "Group:1");
+        assert_eq!(revoke.role.name(), "Viewer");
+        assert_eq!(revoke.object.to_string(), "Post:1");
+
+        // an unknown role, object namespace or subject namespace doesn't fit the schema
+        assert!(grant(&full, "User_alice", "Post_1_Editor").is_none());
+        assert!(grant(&full, "User_alice", "Team_1_Member").is_none());
+        assert!(grant(&full, "Bot_1", "Post_1_Viewer").is_none());
+    }
+}
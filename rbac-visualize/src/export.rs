@@ -0,0 +1,626 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+    fs, io,
+    path::Path,
+};
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use clap::ValueEnum;
+use egui::{Color32, FontDefinitions, Pos2, Rect, Vec2};
+use petgraph::stable_graph::NodeIndex;
+use rbac::{ENTITY_TO_ROLE, INHERITS, ROLE_TO_ENTITY};
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, PremultipliedColorU8, Stroke, Transform};
+
+use crate::{
+    node::{find_intersection, rect_to_chamfered_points, rect_to_points, NodeData, NodeKind},
+    style::{edge_color, edge_width, namespace_color, node_stroke_width},
+    ViewGraph,
+};
+
+const FONT_SIZE: f32 = 12.;
+// advance of a character of the monospace font, relative to its size
+const CHAR_WIDTH: f32 = 0.6;
+const PADDING: f32 = 5.;
+// around the drawing, and between the legend and the graph
+const MARGIN: f32 = 40.;
+const LEGEND_LINE: f32 = 18.;
+const ARROW: f32 = 8.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Dot,
+    Mermaid,
+    Svg,
+    Png,
+}
+
+impl Format {
+    // from the extension of the file exported to
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "dot" | "gv" => Some(Format::Dot),
+            "mmd" | "mermaid" => Some(Format::Mermaid),
+            "svg" => Some(Format::Svg),
+            "png" => Some(Format::Png),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Png(String),
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+// Writes `g` to `path`. DOT and Mermaid leave the layout to the tool rendering them, SVG and
+// PNG are drawn where the vertices of `g` are. Vertices and edges are written sorted by key, so
+// exporting the same store twice gives the same file.
+pub fn export(g: &ViewGraph, format: Format, path: &Path) -> Result<(), ExportError> {
+    let bytes = match format {
+        Format::Dot => to_dot(g).into_bytes(),
+        Format::Mermaid => to_mermaid(g).into_bytes(),
+        Format::Svg => to_svg(&Scene::new(g)).into_bytes(),
+        Format::Png => to_png(&Scene::new(g))?,
+    };
+    fs::write(path, bytes)?;
+
+    Ok(())
+}
+
+fn sorted_nodes(g: &ViewGraph) -> Vec<(NodeIndex, &NodeData, Pos2)> {
+    let mut nodes: Vec<_> = g
+        .nodes_iter()
+        .map(|(idx, n)| (idx, n.payload(), n.location()))
+        .collect();
+    nodes.sort_by(|(_, a, _), (_, b, _)| a.entity.cmp(&b.entity));
+    nodes
+}
+
+// (from, type, to), sorted by the keys of their ends
+fn sorted_edges(g: &ViewGraph) -> Vec<(NodeIndex, &str, NodeIndex)> {
+    let key = |idx: NodeIndex| g.node(idx).map(|n| n.payload().entity.as_str());
+    let mut edges: Vec<_> = g
+        .edges_iter()
+        .filter_map(|(e, edge)| {
+            let (a, b) = g.edge_endpoints(e)?;
+            Some((a, edge.payload().t.as_str(), b))
+        })
+        .collect();
+    edges.sort_by(|(a1, t1, b1), (a2, t2, b2)| {
+        (key(*a1), *t1, key(*b1)).cmp(&(key(*a2), *t2, key(*b2)))
+    });
+    edges
+}
+
+// objects are filled with a light shade of their namespace's color
+fn object_fill(color: Color32) -> Color32 {
+    let mix = |c: u8| 255 - ((255 - c) as f32 * 0.35) as u8;
+    Color32::from_rgb(mix(color.r()), mix(color.g()), mix(color.b()))
+}
+
+fn hex(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+fn to_dot(g: &ViewGraph) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut dot = String::from("digraph rbac {\n    node [fontname=\"monospace\" fontsize=10];\n");
+
+    for (_, n, _) in sorted_nodes(g) {
+        let color = namespace_color(&n.namespace);
+        let style = match n.kind {
+            NodeKind::Entity => "shape=box".to_string(),
+            NodeKind::Object => format!(
+                "shape=box style=filled fillcolor=\"{}\"",
+                hex(object_fill(color))
+            ),
+            NodeKind::Role => "shape=octagon".to_string(),
+        };
+        writeln!(
+            dot,
+            "    {} [{} color=\"{}\" penwidth={}];",
+            quote(&n.entity),
+            style,
+            hex(color),
+            node_stroke_width(n.kind)
+        )
+        .unwrap();
+    }
+
+    for (a, t, b) in sorted_edges(g) {
+        let (a, b) = match (g.node(a), g.node(b)) {
+            (Some(a), Some(b)) => (a.payload(), b.payload()),
+            _ => continue,
+        };
+        writeln!(
+            dot,
+            "    {} -> {} [label={} color=\"{}\" penwidth={}];",
+            quote(&a.entity),
+            quote(&b.entity),
+            quote(t),
+            hex(edge_color(t)),
+            edge_width(t)
+        )
+        .unwrap();
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn to_mermaid(g: &ViewGraph) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('"', "#quot;"));
+    let mut mermaid = String::from("flowchart TB\n");
+
+    // mermaid ids are kept short, keys are only used as labels
+    let mut ids = HashMap::new();
+    for (i, (idx, n, _)) in sorted_nodes(g).into_iter().enumerate() {
+        let id = format!("n{}", i);
+        let label = quote(&n.entity);
+        let shape = match n.kind {
+            NodeKind::Entity => format!("[{}]", label),
+            NodeKind::Object => format!("[[{}]]", label),
+            NodeKind::Role => format!("{{{{{}}}}}", label),
+        };
+        let color = namespace_color(&n.namespace);
+        let fill = match n.kind {
+            NodeKind::Object => hex(object_fill(color)),
+            NodeKind::Entity | NodeKind::Role => "#ffffff".to_string(),
+        };
+        writeln!(mermaid, "    {}{}", id, shape).unwrap();
+        writeln!(
+            mermaid,
+            "    style {} stroke:{},stroke-width:{}px,fill:{}",
+            id,
+            hex(color),
+            node_stroke_width(n.kind),
+            fill
+        )
+        .unwrap();
+        ids.insert(idx, id);
+    }
+
+    // links are styled by their position in the file
+    let edges: Vec<_> = sorted_edges(g)
+        .into_iter()
+        .filter_map(|(a, t, b)| Some((ids.get(&a)?, t, ids.get(&b)?)))
+        .collect();
+    for (a, t, b) in &edges {
+        writeln!(mermaid, "    {} -->|{}| {}", a, t, b).unwrap();
+    }
+    for (i, (_, t, _)) in edges.iter().enumerate() {
+        writeln!(
+            mermaid,
+            "    linkStyle {} stroke:{},stroke-width:{}px",
+            i,
+            hex(edge_color(t)),
+            edge_width(t)
+        )
+        .unwrap();
+    }
+
+    mermaid
+}
+
+// what the SVG and PNG exports draw, in image coordinates
+struct Scene {
+    size: Vec2,
+    // drawn in this order
+    lines: Vec<Line>,
+    polygons: Vec<Polygon>,
+    texts: Vec<Text>,
+}
+
+struct Line {
+    from: Pos2,
+    to: Pos2,
+    color: Color32,
+    width: f32,
+}
+
+struct Polygon {
+    points: Vec<Pos2>,
+    fill: Color32,
+    stroke: Color32,
+    width: f32,
+}
+
+struct Text {
+    // left end of the baseline
+    pos: Pos2,
+    text: String,
+    color: Color32,
+}
+
+impl Scene {
+    // the vertices of `g` where they are, with a legend of their namespaces and the edge types
+    // above them
+    fn new(g: &ViewGraph) -> Scene {
+        let mut scene = Scene {
+            size: Vec2::ZERO,
+            lines: Vec::new(),
+            polygons: Vec::new(),
+            texts: Vec::new(),
+        };
+
+        let nodes = sorted_nodes(g);
+        let namespaces: BTreeSet<&str> =
+            nodes.iter().map(|(_, n, _)| n.namespace.as_str()).collect();
+        let legend: Vec<(Color32, &str)> = namespaces
+            .iter()
+            .map(|namespace| (namespace_color(namespace), *namespace))
+            .chain([ENTITY_TO_ROLE, INHERITS, ROLE_TO_ENTITY].map(|t| (edge_color(t), t)))
+            .collect();
+
+        // text box of every vertex, around its location
+        let boxes: HashMap<NodeIndex, Rect> = nodes
+            .iter()
+            .map(|(idx, n, location)| {
+                let size = Vec2::new(text_width(&n.entity), FONT_SIZE);
+                (*idx, Rect::from_center_size(*location, size))
+            })
+            .collect();
+        let bounds = boxes.values().fold(Rect::NOTHING, |bounds, rect| {
+            bounds.union(rect.expand(PADDING))
+        });
+        let bounds = if bounds.is_positive() {
+            bounds
+        } else {
+            Rect::from_min_size(Pos2::ZERO, Vec2::ZERO)
+        };
+
+        let legend_height = legend.len() as f32 * LEGEND_LINE;
+        let legend_width = legend
+            .iter()
+            .map(|(_, name)| LEGEND_LINE + text_width(name))
+            .fold(0., f32::max);
+        let offset = Vec2::new(MARGIN, 2. * MARGIN + legend_height) - bounds.min.to_vec2();
+        scene.size = Vec2::new(
+            bounds.width().max(legend_width) + 2. * MARGIN,
+            bounds.height() + legend_height + 3. * MARGIN,
+        );
+
+        for (i, (color, name)) in legend.into_iter().enumerate() {
+            let top = MARGIN + i as f32 * LEGEND_LINE;
+            let swatch = Rect::from_min_size(Pos2::new(MARGIN, top), Vec2::splat(FONT_SIZE));
+            scene.polygons.push(Polygon {
+                points: rect_to_points(swatch, 0.),
+                fill: color,
+                stroke: color,
+                width: 0.,
+            });
+            scene.texts.push(Text {
+                pos: Pos2::new(MARGIN + LEGEND_LINE, top + FONT_SIZE * 0.85),
+                text: name.to_string(),
+                color: Color32::BLACK,
+            });
+        }
+
+        for (a, t, b) in sorted_edges(g) {
+            let (a, b) = match (boxes.get(&a), boxes.get(&b)) {
+                (Some(a), Some(b)) => (
+                    a.expand(PADDING).translate(offset),
+                    b.expand(PADDING).translate(offset),
+                ),
+                _ => continue,
+            };
+            let dir = b.center() - a.center();
+            if dir == Vec2::ZERO {
+                continue;
+            }
+
+            let from = find_intersection(a.center(), a.width(), a.height(), dir);
+            let to = find_intersection(b.center(), b.width(), b.height(), -dir);
+            let color = edge_color(t);
+            let (along, across) = (
+                dir.normalized() * ARROW,
+                dir.normalized().rot90() * ARROW / 2.,
+            );
+            scene.lines.push(Line {
+                from,
+                to: to - along,
+                color,
+                width: edge_width(t),
+            });
+            scene.polygons.push(Polygon {
+                points: vec![to, to - along + across, to - along - across],
+                fill: color,
+                stroke: color,
+                width: 0.,
+            });
+        }
+
+        for (idx, n, _) in &nodes {
+            let rect = boxes[idx].translate(offset);
+            let color = namespace_color(&n.namespace);
+            let points = match n.kind {
+                NodeKind::Role => rect_to_chamfered_points(rect, PADDING),
+                NodeKind::Entity | NodeKind::Object => rect_to_points(rect, PADDING),
+            };
+            let fill = match n.kind {
+                NodeKind::Object => object_fill(color),
+                NodeKind::Entity | NodeKind::Role => Color32::WHITE,
+            };
+            scene.polygons.push(Polygon {
+                points,
+                fill,
+                stroke: color,
+                width: node_stroke_width(n.kind),
+            });
+            scene.texts.push(Text {
+                pos: Pos2::new(rect.min.x, rect.max.y - FONT_SIZE * 0.15),
+                text: n.entity.clone(),
+                color: Color32::BLACK,
+            });
+        }
+
+        scene
+    }
+}
+
+fn text_width(text: &str) -> f32 {
+    text.chars().count() as f32 * FONT_SIZE * CHAR_WIDTH
+}
+
+fn to_svg(scene: &Scene) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = scene.size.x.ceil(),
+        h = scene.size.y.ceil()
+    );
+    svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n");
+
+    for line in &scene.lines {
+        writeln!(
+            svg,
+            "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            line.from.x,
+            line.from.y,
+            line.to.x,
+            line.to.y,
+            hex(line.color),
+            line.width
+        )
+        .unwrap();
+    }
+    for polygon in &scene.polygons {
+        let points: Vec<String> = polygon
+            .points
+            .iter()
+            .map(|p| format!("{:.1},{:.1}", p.x, p.y))
+            .collect();
+        writeln!(
+            svg,
+            "<polygon points=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            points.join(" "),
+            hex(polygon.fill),
+            hex(polygon.stroke),
+            polygon.width
+        )
+        .unwrap();
+    }
+    for text in &scene.texts {
+        writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"monospace\" font-size=\"{}\" fill=\"{}\">{}</text>",
+            text.pos.x,
+            text.pos.y,
+            FONT_SIZE,
+            hex(text.color),
+            escape(&text.text)
+        )
+        .unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn to_png(scene: &Scene) -> Result<Vec<u8>, ExportError> {
+    let mut pixmap = Pixmap::new(scene.size.x.ceil() as u32, scene.size.y.ceil() as u32)
+        .ok_or_else(|| ExportError::Png("the graph is too large for an image".to_string()))?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+
+    for line in &scene.lines {
+        let mut path = PathBuilder::new();
+        path.move_to(line.from.x, line.from.y);
+        path.line_to(line.to.x, line.to.y);
+        if let Some(path) = path.finish() {
+            let stroke = Stroke {
+                width: line.width,
+                ..Stroke::default()
+            };
+            pixmap.stroke_path(
+                &path,
+                &paint(line.color),
+                &stroke,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    for polygon in &scene.polygons {
+        let mut path = PathBuilder::new();
+        for (i, p) in polygon.points.iter().enumerate() {
+            if i == 0 {
+                path.move_to(p.x, p.y);
+            } else {
+                path.line_to(p.x, p.y);
+            }
+        }
+        path.close();
+        let path = match path.finish() {
+            Some(path) => path,
+            None => continue,
+        };
+
+        pixmap.fill_path(
+            &path,
+            &paint(polygon.fill),
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+        if polygon.width > 0. {
+            let stroke = Stroke {
+                width: polygon.width,
+                ..Stroke::default()
+            };
+            pixmap.stroke_path(
+                &path,
+                &paint(polygon.stroke),
+                &stroke,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    // the monospace font the app uses, so labels fit the boxes drawn for them
+    let fonts = FontDefinitions::default();
+    let font = fonts
+        .font_data
+        .get("Hack")
+        .and_then(|data| FontRef::try_from_slice(&data.font).ok())
+        .ok_or_else(|| ExportError::Png("no monospace font".to_string()))?;
+    for text in &scene.texts {
+        draw_text(&mut pixmap, &font, text);
+    }
+
+    pixmap
+        .encode_png()
+        .map_err(|e| ExportError::Png(e.to_string()))
+}
+
+fn paint(color: Color32) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(color.r(), color.g(), color.b(), color.a());
+    paint.anti_alias = true;
+    paint
+}
+
+fn draw_text(pixmap: &mut Pixmap, font: &FontRef, text: &Text) {
+    let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+    let pixels = pixmap.pixels_mut();
+    let scaled = font.as_scaled(PxScale::from(FONT_SIZE));
+
+    let mut x = text.pos.x;
+    for c in text.text.chars() {
+        let mut glyph = scaled.scaled_glyph(c);
+        glyph.position = point(x, text.pos.y);
+        x += scaled.h_advance(glyph.id);
+
+        let outlined = match font.outline_glyph(glyph) {
+            Some(outlined) => outlined,
+            // e.g. spaces
+            None => continue,
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= width || py >= height {
+                return;
+            }
+            blend(
+                &mut pixels[(py * width + px) as usize],
+                text.color,
+                coverage,
+            );
+        });
+    }
+}
+
+// covers `dst` with the opaque `color` as far as `coverage` says
+fn blend(dst: &mut PremultipliedColorU8, color: Color32, coverage: f32) {
+    let coverage = coverage.clamp(0., 1.);
+    let mix = |s: u8, d: u8| (s as f32 * coverage + d as f32 * (1. - coverage)).round() as u8;
+
+    let blended = PremultipliedColorU8::from_rgba(
+        mix(color.r(), dst.red()),
+        mix(color.g(), dst.green()),
+        mix(color.b(), dst.blue()),
+        mix(255, dst.alpha()),
+    );
+    if let Some(blended) = blended {
+        *dst = blended;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui_graphs::Graph;
+    use petgraph::stable_graph::StableGraph;
+
+    use super::*;
+    use crate::{
+        edge::EdgeData,
+        fixture::{self, node},
+        layout::Layout,
+    };
+
+    // `full` laid out in layers, as the app shows it
+    fn view(full: &StableGraph<NodeData, EdgeData>) -> ViewGraph {
+        let mut g: ViewGraph = Graph::from(full);
+        Layout::Layered.apply(&mut g);
+        g
+    }
+
+    // the same vertices and edges as `full`, added in the opposite order
+    fn reversed(full: &StableGraph<NodeData, EdgeData>) -> StableGraph<NodeData, EdgeData> {
+        let mut g = StableGraph::new();
+        let mut indices = HashMap::new();
+        let nodes: Vec<NodeIndex> = full.node_indices().collect();
+        for idx in nodes.into_iter().rev() {
+            indices.insert(idx, g.add_node(full[idx].clone()));
+        }
+        let edges: Vec<_> = full.edge_indices().collect();
+        for e in edges.into_iter().rev() {
+            let (a, b) = full.edge_endpoints(e).unwrap();
+            g.add_edge(indices[&a], indices[&b], full[e].clone());
+        }
+        g
+    }
+
+    #[test]
+    fn export_is_deterministic() {
+        let full = fixture::graph();
+        let (g, again) = (view(&full), view(&reversed(&full)));
+
+        assert_eq!(to_dot(&g), to_dot(&again));
+        assert_eq!(to_mermaid(&g), to_mermaid(&again));
+        assert_eq!(to_svg(&Scene::new(&g)), to_svg(&Scene::new(&again)));
+
+        // sorted by key
+        let dot = to_dot(&g);
+        let alice = dot.find("\"User_alice\" [").unwrap();
+        let group = dot.find("\"Group_1\" [").unwrap();
+        assert!(group < alice);
+    }
+
+    #[test]
+    fn export_escapes_keys() {
+        let mut full = fixture::graph();
+        full.add_node(node("User_a\"<&>", NodeKind::Entity));
+        let g = view(&full);
+
+        assert!(to_dot(&g).contains("\"User_a\\\"<&>\" ["));
+        assert!(to_mermaid(&g).contains("[\"User_a#quot;<&>\"]"));
+        assert!(to_svg(&Scene::new(&g)).contains(">User_a&quot;&lt;&amp;&gt;</text>"));
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use egui::{DragValue, Ui};
+use petgraph::{
+    stable_graph::{NodeIndex, StableGraph},
+    visit::EdgeRef,
+};
+use rbac::{ENTITY_TO_ROLE, INHERITS, ROLE_TO_ENTITY};
+
+use crate::{edge::EdgeData, node::NodeData};
+
+// most search results listed at once
+const MAX_RESULTS: usize = 20;
+
+// What part of the store's graph is shown. Everything is computed from the full graph, the view
+// is then synced to it so shown vertices keep their positions.
+pub struct Filter {
+    search: String,
+    hidden_namespaces: HashSet<String>,
+    hidden_edge_types: HashSet<String>,
+    // entity key of the vertex whose neighbourhood is shown
+    focus: Option<String>,
+    hops: usize,
+}
+
+// what the user did in the filter panel
+#[derive(Default)]
+pub struct FilterAction {
+    // the shown part of the graph changed
+    pub changed: bool,
+    // entity key of a search result clicked on
+    pub found: Option<String>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            search: String::new(),
+            hidden_namespaces: HashSet::new(),
+            hidden_edge_types: HashSet::new(),
+            focus: None,
+            hops: 2,
+        }
+    }
+}
+
+impl Filter {
+    // the vertices and edges of `full` passing the filters
+    pub fn apply(&self, full: &StableGraph<NodeData, EdgeData>) -> StableGraph<NodeData, EdgeData> {
+        let mut shown: HashSet<NodeIndex> = full
+            .node_indices()
+            .filter(|idx| !self.hidden_namespaces.contains(&full[*idx].namespace))
+            .collect();
+        let edge_shown = |t: &str, a: NodeIndex, b: NodeIndex, shown: &HashSet<NodeIndex>| {
+            !self.hidden_edge_types.contains(t) && shown.contains(&a) && shown.contains(&b)
+        };
+
+        if let Some(focus) = &self.focus {
+            let start = full.node_indices().find(|idx| full[*idx].entity == *focus);
+            shown = match start {
+                Some(start) if shown.contains(&start) => {
+                    // breadth first over shown edges, in both directions
+                    let mut hops = HashMap::from([(start, 0)]);
+                    let mut queue = VecDeque::from([start]);
+                    while let Some(idx) = queue.pop_front() {
+                        if hops[&idx] == self.hops {
+                            continue;
+                        }
+                        let neighbours: Vec<NodeIndex> = full
+                            .edges_directed(idx, petgraph::Direction::Outgoing)
+                            .chain(full.edges_directed(idx, petgraph::Direction::Incoming))
+                            .filter(|e| edge_shown(&e.weight().t, e.source(), e.target(), &shown))
+                            .map(|e| {
+                                if e.source() == idx {
+                                    e.target()
+                                } else {
+                                    e.source()
+                                }
+                            })
+                            .collect();
+                        for n in neighbours {
+                            if !hops.contains_key(&n) {
+                                hops.insert(n, hops[&idx] + 1);
+                                queue.push_back(n);
+                            }
+                        }
+                    }
+                    hops.into_keys().collect()
+                }
+                // the focused vertex is gone or filtered out
+                _ => HashSet::new(),
+            };
+        }
+
+        let mut g = StableGraph::new();
+        let mut index = HashMap::new();
+        for idx in full.node_indices().filter(|idx| shown.contains(idx)) {
+            index.insert(idx, g.add_node(full[idx].clone()));
+        }
+        for e in full.edge_indices() {
+            let (a, b) = match full.edge_endpoints(e) {
+                Some(ends) => ends,
+                None => continue,
+            };
+            if edge_shown(&full[e].t, a, b, &shown) {
+                g.add_edge(index[&a], index[&b], full[e].clone());
+            }
+        }
+
+        g
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        full: &StableGraph<NodeData, EdgeData>,
+        namespaces: &[String],
+        selected: Option<&str>,
+    ) -> FilterAction {
+        let mut action = FilterAction::default();
+
+        ui.heading("Filter");
+
+        ui.label("search");
+        ui.text_edit_singleline(&mut self.search);
+        let search = self.search.trim().to_lowercase();
+        if !search.is_empty() {
+            let mut found = full
+                .node_weights()
+                .filter(|n| n.entity.to_lowercase().contains(&search));
+            for n in found.by_ref().take(MAX_RESULTS) {
+                if ui.link(&n.entity).clicked() {
+                    action.found = Some(n.entity.clone());
+                }
+            }
+            let more = found.count();
+            if more > 0 {
+                ui.label(format!("and {} more", more));
+            }
+        }
+
+        ui.separator();
+        ui.strong("namespaces");
+        for namespace in namespaces {
+            action.changed |= toggle(ui, &mut self.hidden_namespaces, namespace);
+        }
+
+        ui.strong("edges");
+        for t in [ENTITY_TO_ROLE, INHERITS, ROLE_TO_ENTITY] {
+            action.changed |= toggle(ui, &mut self.hidden_edge_types, t);
+        }
+
+        ui.separator();
+        ui.strong("focus");
+        ui.horizontal(|ui| {
+            ui.label("hops");
+            let hops = ui.add(DragValue::new(&mut self.hops).range(1..=10));
+            action.changed |= hops.changed() && self.focus.is_some();
+        });
+        match &self.focus {
+            Some(focus) => {
+                ui.monospace(focus);
+                if ui.button("Show everything").clicked() {
+                    self.focus = None;
+                    action.changed = true;
+                }
+            }
+            None => {
+                let button = ui.add_enabled(
+                    selected.is_some(),
+                    egui::Button::new("Focus on the selected vertex"),
+                );
+                if button.clicked() {
+                    self.focus = selected.map(str::to_string);
+                    action.changed = true;
+                }
+            }
+        }
+
+        action
+    }
+
+    // vertices filtered out are shown again by clicking on a search result
+    pub fn reveal(&mut self, entity: &str, full: &StableGraph<NodeData, EdgeData>) {
+        if let Some(n) = full.node_weights().find(|n| n.entity == entity) {
+            self.hidden_namespaces.remove(&n.namespace);
+        }
+        if self.focus.is_some() {
+            self.focus = Some(entity.to_string());
+        }
+    }
+}
+
+// a checkbox showing `name` while it's not in `hidden`, returns true when clicked
+fn toggle(ui: &mut Ui, hidden: &mut HashSet<String>, name: &str) -> bool {
+    let mut shown = !hidden.contains(name);
+    if !ui.checkbox(&mut shown, name).changed() {
+        return false;
+    }
+
+    if shown {
+        hidden.remove(name);
+    } else {
+        hidden.insert(name.to_string());
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn shown(filter: &Filter) -> (Vec<String>, usize) {
+        let g = filter.apply(&fixture::graph());
+        let mut nodes: Vec<String> = g.node_weights().map(|n| n.entity.clone()).collect();
+        nodes.sort();
+        (nodes, g.edge_count())
+    }
+
+    fn focused(entity: &str, hops: usize) -> Filter {
+        Filter {
+            focus: Some(entity.to_string()),
+            hops,
+            ..Filter::default()
+        }
+    }
+
+    #[test]
+    fn filter_by_namespace_and_edge_type() {
+        assert_eq!(shown(&Filter::default()).0.len(), 6);
+
+        let mut filter = Filter::default();
+        filter.hidden_namespaces.insert("Post".to_string());
+        assert_eq!(
+            shown(&filter),
+            (
+                vec![
+                    "Group_1".to_string(),
+                    "Group_1_Member".to_string(),
+                    "User_alice".to_string()
+                ],
+                2
+            )
+        );
+
+        let mut filter = Filter::default();
+        filter.hidden_edge_types.insert(ROLE_TO_ENTITY.to_string());
+        assert_eq!(shown(&filter), (shown(&Filter::default()).0, 3));
+    }
+
+    #[test]
+    fn filter_focus_hops() {
+        let (nodes, edges) = shown(&focused("User_alice", 1));
+        assert_eq!(
+            nodes,
+            vec!["Group_1_Member".to_string(), "User_alice".to_string()]
+        );
+        assert_eq!(edges, 1);
+
+        let (nodes, edges) = shown(&focused("User_alice", 3));
+        assert_eq!(
+            nodes,
+            vec![
+                "Group_1".to_string(),
+                "Group_1_Member".to_string(),
+                "Post_1_Viewer".to_string(),
+                "User_alice".to_string()
+            ]
+        );
+        assert_eq!(edges, 3);
+
+        // edges in both directions count, the writer reaches the viewer and the post
+        let (nodes, _) = shown(&focused("Post_1_Viewer", 1));
+        assert_eq!(nodes.len(), 4);
+        assert!(nodes.contains(&"Post_1_Writer".to_string()));
+        assert!(nodes.contains(&"Group_1".to_string()));
+
+        // hidden edges and namespaces aren't walked through
+        let mut filter = focused("User_alice", 3);
+        filter.hidden_edge_types.insert(ROLE_TO_ENTITY.to_string());
+        assert_eq!(shown(&filter).0.len(), 2);
+
+        let mut filter = focused("User_alice", 3);
+        filter.hidden_namespaces.insert("Group".to_string());
+        assert_eq!(shown(&filter), (vec!["User_alice".to_string()], 0));
+
+        // a focus that's gone shows nothing
+        assert_eq!(shown(&focused("User_bob", 3)), (Vec::new(), 0));
+    }
+}
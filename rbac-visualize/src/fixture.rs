@@ -0,0 +1,89 @@
+use petgraph::stable_graph::{NodeIndex, StableGraph};
+use rbac::{schema::Schema, ENTITY_TO_ROLE, INHERITS, ROLE_TO_ENTITY};
+
+use crate::{
+    edge::EdgeData,
+    node::{NodeData, NodeKind},
+    ViewGraph,
+};
+
+// Small store for the tests, as `generate_graph` reads it: alice is a member of group 1, whose
+// members view post 1, and writers of the post view it too.
+pub fn graph() -> StableGraph<NodeData, EdgeData> {
+    let mut g = StableGraph::new();
+    for (entity, kind) in [
+        ("User_alice", NodeKind::Entity),
+        ("Group_1", NodeKind::Object),
+        ("Group_1_Member", NodeKind::Role),
+        ("Post_1", NodeKind::Object),
+        ("Post_1_Writer", NodeKind::Role),
+        ("Post_1_Viewer", NodeKind::Role),
+    ] {
+        g.add_node(node(entity, kind));
+    }
+
+    for (a, t, b) in [
+        ("User_alice", ENTITY_TO_ROLE, "Group_1_Member"),
+        ("Group_1_Member", ROLE_TO_ENTITY, "Group_1"),
+        ("Group_1", ENTITY_TO_ROLE, "Post_1_Viewer"),
+        ("Post_1_Writer", INHERITS, "Post_1_Viewer"),
+        ("Post_1_Writer", ROLE_TO_ENTITY, "Post_1"),
+        ("Post_1_Viewer", ROLE_TO_ENTITY, "Post_1"),
+    ] {
+        let (a, b) = (find(&g, a), find(&g, b));
+        g.add_edge(a, b, edge(t));
+    }
+
+    g
+}
+
+// the community model the store of `graph` follows, group admins write the posts the group owns
+pub fn schema() -> Schema {
+    Schema::from_json(
+        r#"{
+            "namespaces": [
+                { "name": "User" },
+                { "name": "Post", "roles": [
+                    { "name": "Writer", "implied_by": [
+                        { "namespace": "Group", "role": "Admin", "via": "OwnedBy" }
+                    ] },
+                    { "name": "Viewer", "child_of": "Writer" },
+                    { "name": "OwnedBy" }
+                ] },
+                { "name": "Group", "roles": [
+                    { "name": "Admin" },
+                    { "name": "Member", "child_of": "Admin", "membership": true }
+                ] }
+            ]
+        }"#,
+    )
+    .unwrap()
+}
+
+pub fn node(entity: &str, kind: NodeKind) -> NodeData {
+    NodeData {
+        entity: entity.to_string(),
+        namespace: entity.split('_').next().unwrap_or_default().to_string(),
+        kind,
+        highlighted: false,
+    }
+}
+
+pub fn edge(t: &str) -> EdgeData {
+    EdgeData {
+        t: t.to_string(),
+        highlighted: false,
+    }
+}
+
+pub fn find(g: &StableGraph<NodeData, EdgeData>, entity: &str) -> NodeIndex {
+    g.node_indices()
+        .find(|idx| g[*idx].entity == entity)
+        .unwrap()
+}
+
+pub fn find_shown(g: &ViewGraph, entity: &str) -> Option<NodeIndex> {
+    g.nodes_iter()
+        .find(|(_, n)| n.payload().entity == entity)
+        .map(|(idx, _)| idx)
+}
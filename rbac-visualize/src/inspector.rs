@@ -0,0 +1,157 @@
+use egui::Ui;
+use petgraph::{
+    stable_graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+    Direction,
+};
+use rbac::{decode_key_part, ENTITY_TO_ROLE, INHERITS, RBAC, ROLE_TO_ENTITY};
+
+use crate::ViewGraph;
+
+const EDGE_TYPES: [&str; 3] = [ENTITY_TO_ROLE, INHERITS, ROLE_TO_ENTITY];
+
+// "inspector" panel showing the selected vertex or edge
+#[derive(Default)]
+pub struct Inspector {
+    // properties of the shown vertex, read once per selection
+    properties: Option<(String, Result<Vec<(String, String)>, String>)>,
+}
+
+impl Inspector {
+    // properties are read again the next time they're shown
+    pub fn invalidate(&mut self) {
+        self.properties = None;
+    }
+
+    // returns the neighbour clicked on, if any
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        g: &ViewGraph,
+        rbac: &RBAC,
+        node: Option<NodeIndex>,
+        edge: Option<EdgeIndex>,
+    ) -> Option<NodeIndex> {
+        ui.heading("Inspector");
+
+        if let Some(idx) = node.filter(|idx| g.node(*idx).is_some()) {
+            return self.show_node(ui, g, rbac, idx);
+        }
+        if let Some(e) = edge.filter(|e| g.edge(*e).is_some()) {
+            return show_edge(ui, g, e);
+        }
+
+        ui.label("select a vertex or an edge");
+        None
+    }
+
+    fn show_node(
+        &mut self,
+        ui: &mut Ui,
+        g: &ViewGraph,
+        rbac: &RBAC,
+        idx: NodeIndex,
+    ) -> Option<NodeIndex> {
+        let key = g.node(idx)?.payload().entity.clone();
+        let mut clicked = None;
+
+        ui.monospace(&key);
+        // `{Namespace}_{id}` for entities, `{Namespace}_{object id}_{Role}` for roles
+        let parts: Option<Vec<String>> = key.split('_').map(decode_key_part).collect();
+        match parts.as_deref() {
+            Some([namespace, id]) => {
+                ui.label(format!("namespace: {}", namespace));
+                ui.label(format!("id: {}", id));
+            }
+            Some([namespace, object_id, role]) => {
+                ui.label(format!("namespace: {}", namespace));
+                ui.label(format!("object id: {}", object_id));
+                ui.label(format!("role: {}", role));
+            }
+            // hashed or unknown keys
+            _ => {}
+        }
+
+        if self.properties.as_ref().map(|(shown, _)| shown) != Some(&key) {
+            let properties = rbac
+                .get_vertex_properties(&key)
+                .map(|props| {
+                    props
+                        .into_iter()
+                        .map(|(name, value)| (name, value.to_string()))
+                        .collect()
+                })
+                .map_err(|e| format!("{:?}", e));
+            self.properties = Some((key.clone(), properties));
+        }
+
+        ui.separator();
+        ui.strong("properties");
+        match &self.properties {
+            Some((_, Ok(properties))) => {
+                for (name, value) in properties {
+                    ui.label(format!("{}: {}", name, value));
+                }
+            }
+            Some((_, Err(e))) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+            None => {}
+        }
+
+        for (title, direction) in [
+            ("outgoing", Direction::Outgoing),
+            ("incoming", Direction::Incoming),
+        ] {
+            ui.separator();
+            ui.strong(title);
+
+            for t in EDGE_TYPES {
+                let neighbours: Vec<(NodeIndex, String)> = g
+                    .edges_directed(idx, direction)
+                    .filter(|e| e.weight().payload().t == t)
+                    .filter_map(|e| {
+                        let other = match direction {
+                            Direction::Outgoing => e.target(),
+                            Direction::Incoming => e.source(),
+                        };
+                        Some((other, g.node(other)?.payload().entity.clone()))
+                    })
+                    .collect();
+                if neighbours.is_empty() {
+                    continue;
+                }
+
+                ui.label(t);
+                ui.indent(t, |ui| {
+                    for (other, key) in neighbours {
+                        if ui.link(key).clicked() {
+                            clicked = Some(other);
+                        }
+                    }
+                });
+            }
+        }
+
+        clicked
+    }
+}
+
+fn show_edge(ui: &mut Ui, g: &ViewGraph, e: EdgeIndex) -> Option<NodeIndex> {
+    let (a, b) = g.edge_endpoints(e)?;
+    let mut clicked = None;
+
+    ui.label(format!("type: {}", g.edge(e)?.payload().t));
+    for (title, idx) in [("from", a), ("to", b)] {
+        ui.horizontal(|ui| {
+            ui.label(title);
+            if let Some(n) = g.node(idx) {
+                if ui.link(&n.payload().entity).clicked() {
+                    clicked = Some(idx);
+                }
+            }
+        });
+    }
+
+    clicked
+}
@@ -0,0 +1,335 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    f32::consts::TAU,
+};
+
+use clap::ValueEnum;
+use egui::{Pos2, Vec2};
+use petgraph::{stable_graph::NodeIndex, visit::EdgeRef, Direction};
+use rbac::{ENTITY_TO_ROLE, ROLE_TO_ENTITY};
+
+use crate::{
+    node::{NodeData, NodeKind},
+    ViewGraph,
+};
+
+// distance between neighbouring vertices of a layout
+const NODE_GAP: f32 = 120.;
+// distance between the rows of the layered layout
+const LAYER_GAP: f32 = 200.;
+// distance between the namespace circles of the circular layout
+const CIRCLE_GAP: f32 = 200.;
+// pairs of vertices visited over all iterations of the force-directed layout, so large graphs
+// get fewer iterations instead of freezing the app
+const FORCE_BUDGET: usize = 50_000_000;
+const FORCE_ITERATIONS: usize = 100;
+// fewer iterations don't settle the layout, graphs too large for them are laid out in layers
+const FORCE_MIN_ITERATIONS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, ValueEnum)]
+pub enum Layout {
+    // subjects on top, their roles below, the objects the roles are on at the bottom
+    #[default]
+    Layered,
+    // layered when the graph is too large to simulate
+    ForceDirected,
+    // one circle per namespace
+    Circular,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 3] = [Layout::Layered, Layout::ForceDirected, Layout::Circular];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Layered => "layered",
+            Layout::ForceDirected => "force-directed",
+            Layout::Circular => "circular by namespace",
+        }
+    }
+
+    // moves every vertex of `g` to its place in the layout
+    pub fn apply(&self, g: &mut ViewGraph) {
+        let nodes: Vec<(NodeIndex, NodeData)> = g
+            .nodes_iter()
+            .map(|(idx, n)| (idx, n.payload().clone()))
+            .collect();
+
+        let locations = match self {
+            Layout::Layered => layered(g, &nodes),
+            Layout::ForceDirected => {
+                force_directed(g, &nodes).unwrap_or_else(|| layered(g, &nodes))
+            }
+            Layout::Circular => circular(&nodes),
+        };
+
+        for (idx, location) in locations {
+            if let Some(n) = g.node_mut(idx) {
+                n.set_location(location);
+            }
+        }
+    }
+}
+
+fn layered(g: &ViewGraph, nodes: &[(NodeIndex, NodeData)]) -> HashMap<NodeIndex, Pos2> {
+    let mut locations = HashMap::new();
+
+    // objects are ordered by namespace and key
+    let mut objects: Vec<&(NodeIndex, NodeData)> = nodes
+        .iter()
+        .filter(|(_, n)| n.kind == NodeKind::Object)
+        .collect();
+    objects.sort_by(|(_, a), (_, b)| (&a.namespace, &a.entity).cmp(&(&b.namespace, &b.entity)));
+    place_row(&mut locations, objects.iter().map(|(idx, _)| *idx), 2.);
+
+    // roles sit above the object they're on
+    let mut roles: Vec<(f32, &(NodeIndex, NodeData))> = nodes
+        .iter()
+        .filter(|(_, n)| n.kind == NodeKind::Role)
+        .map(|node| (mean_x(g, &locations, node.0, ROLE_TO_ENTITY), node))
+        .collect();
+    roles.sort_by(|(a, na), (b, nb)| a.total_cmp(b).then_with(|| na.1.entity.cmp(&nb.1.entity)));
+    place_row(&mut locations, roles.iter().map(|(_, (idx, _))| *idx), 1.);
+
+    // subjects sit above the roles they hold
+    let mut entities: Vec<(f32, &(NodeIndex, NodeData))> = nodes
+        .iter()
+        .filter(|(_, n)| n.kind == NodeKind::Entity)
+        .map(|node| (mean_x(g, &locations, node.0, ENTITY_TO_ROLE), node))
+        .collect();
+    entities.sort_by(|(a, na), (b, nb)| a.total_cmp(b).then_with(|| na.1.entity.cmp(&nb.1.entity)));
+    place_row(
+        &mut locations,
+        entities.iter().map(|(_, (idx, _))| *idx),
+        0.,
+    );
+
+    locations
+}
+
+// mean x of the already placed vertices `idx` points at with `t` edges, vertices without any
+// go to the end of their row
+fn mean_x(g: &ViewGraph, locations: &HashMap<NodeIndex, Pos2>, idx: NodeIndex, t: &str) -> f32 {
+    let xs: Vec<f32> = g
+        .edges_directed(idx, Direction::Outgoing)
+        .filter(|e| e.weight().payload().t == t)
+        .filter_map(|e| locations.get(&e.target()).map(|pos| pos.x))
+        .collect();
+
+    if xs.is_empty() {
+        f32::MAX
+    } else {
+        xs.iter().sum::<f32>() / xs.len() as f32
+    }
+}
+
+// lays `row` out left to right, centered on x = 0
+fn place_row(
+    locations: &mut HashMap<NodeIndex, Pos2>,
+    row: impl ExactSizeIterator<Item = NodeIndex>,
+    layer: f32,
+) {
+    let offset = (row.len() as f32 - 1.) / 2.;
+    for (i, idx) in row.enumerate() {
+        locations.insert(
+            idx,
+            Pos2::new((i as f32 - offset) * NODE_GAP, layer * LAYER_GAP),
+        );
+    }
+}
+
+// Fruchterman-Reingold: vertices push each other away, edges pull their ends together, and
+// the distance moved per iteration cools down. Starts from a circle so the result is the same
+// on every run. None when the budget doesn't allow enough iterations for `nodes`.
+fn force_directed(
+    g: &ViewGraph,
+    nodes: &[(NodeIndex, NodeData)],
+) -> Option<HashMap<NodeIndex, Pos2>> {
+    let n = nodes.len();
+    if n == 0 {
+        return Some(HashMap::new());
+    }
+    let iterations = (FORCE_BUDGET / n.saturating_mul(n)).min(FORCE_ITERATIONS);
+    if iterations < FORCE_MIN_ITERATIONS {
+        return None;
+    }
+
+    let index: HashMap<NodeIndex, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (idx, _))| (*idx, i))
+        .collect();
+    let edges: Vec<(usize, usize)> = g
+        .edges_iter()
+        .filter_map(|(e, _)| g.edge_endpoints(e))
+        .filter_map(|(a, b)| Some((*index.get(&a)?, *index.get(&b)?)))
+        .filter(|(a, b)| a != b)
+        .collect();
+
+    let radius = NODE_GAP * (n as f32).sqrt();
+    let mut pos: Vec<Vec2> = (0..n)
+        .map(|i| Vec2::angled(TAU * i as f32 / n as f32) * radius)
+        .collect();
+
+    let k = NODE_GAP;
+    for iteration in 0..iterations {
+        let mut disp = vec![Vec2::ZERO; n];
+
+        for i in 0..n {
+            for j in i + 1..n {
+                let d = pos[i] - pos[j];
+                let dist = d.length().max(0.01);
+                let push = d / dist * (k * k / dist);
+                disp[i] += push;
+                disp[j] -= push;
+            }
+        }
+        for (a, b) in &edges {
+            let d = pos[*a] - pos[*b];
+            let dist = d.length().max(0.01);
+            let pull = d / dist * (dist * dist / k);
+            disp[*a] -= pull;
+            disp[*b] += pull;
+        }
+
+        let temperature = radius * (1. - iteration as f32 / iterations as f32);
+        for (p, d) in pos.iter_mut().zip(disp) {
+            let len = d.length();
+            if len > 0. {
+                *p += d / len * len.min(temperature);
+            }
+        }
+    }
+
+    Some(
+        nodes
+            .iter()
+            .zip(pos)
+            .map(|((idx, _), p)| (*idx, p.to_pos2()))
+            .collect(),
+    )
+}
+
+// namespaces are laid out left to right, each on a circle of its own with objects, roles and
+// entities grouped
+fn circular(nodes: &[(NodeIndex, NodeData)]) -> HashMap<NodeIndex, Pos2> {
+    let mut namespaces: BTreeMap<&str, Vec<&(NodeIndex, NodeData)>> = BTreeMap::new();
+    for node in nodes {
+        namespaces.entry(&node.1.namespace).or_default().push(node);
+    }
+
+    let mut locations = HashMap::new();
+    let mut left = 0.;
+    for members in namespaces.values_mut() {
+        members.sort_by_key(|(_, n)| (kind_order(n.kind), n.entity.clone()));
+
+        let count = members.len();
+        let radius = if count == 1 {
+            0.
+        } else {
+            (NODE_GAP * count as f32 / TAU).max(NODE_GAP / 2.)
+        };
+        let center = Vec2::new(left + radius, 0.);
+        for (i, (idx, _)) in members.iter().enumerate() {
+            let angle = TAU * i as f32 / count as f32;
+            locations.insert(*idx, (center + Vec2::angled(angle) * radius).to_pos2());
+        }
+
+        left += 2. * radius + CIRCLE_GAP;
+    }
+
+    locations
+}
+
+fn kind_order(kind: NodeKind) -> u8 {
+    match kind {
+        NodeKind::Object => 0,
+        NodeKind::Role => 1,
+        NodeKind::Entity => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui_graphs::Graph;
+    use petgraph::stable_graph::StableGraph;
+
+    use super::*;
+    use crate::fixture::{self, node};
+
+    fn laid_out(
+        layout: Layout,
+        full: &StableGraph<NodeData, crate::edge::EdgeData>,
+    ) -> HashMap<String, Pos2> {
+        let mut g: ViewGraph = Graph::from(full);
+        layout.apply(&mut g);
+        g.nodes_iter()
+            .map(|(_, n)| (n.payload().entity.clone(), n.location()))
+            .collect()
+    }
+
+    #[test]
+    fn layered_rows() {
+        let at = laid_out(Layout::Layered, &fixture::graph());
+
+        // subjects, then roles above their objects, then objects
+        assert_eq!(at["User_alice"], Pos2::new(0., 0.));
+        assert_eq!(at["Group_1_Member"], Pos2::new(-NODE_GAP, LAYER_GAP));
+        assert_eq!(at["Post_1_Viewer"], Pos2::new(0., LAYER_GAP));
+        assert_eq!(at["Post_1_Writer"], Pos2::new(NODE_GAP, LAYER_GAP));
+        assert_eq!(at["Group_1"], Pos2::new(-NODE_GAP / 2., 2. * LAYER_GAP));
+        assert_eq!(at["Post_1"], Pos2::new(NODE_GAP / 2., 2. * LAYER_GAP));
+    }
+
+    #[test]
+    fn circular_per_namespace() {
+        let at = laid_out(Layout::Circular, &fixture::graph());
+
+        let mut spans = Vec::new();
+        for namespace in ["Group", "Post", "User"] {
+            let members: Vec<Pos2> = at
+                .iter()
+                .filter(|(entity, _)| entity.starts_with(namespace))
+                .map(|(_, pos)| *pos)
+                .collect();
+            let center = members
+                .iter()
+                .fold(Vec2::ZERO, |sum, pos| sum + pos.to_vec2())
+                / members.len() as f32;
+            let radius = (members[0] - center.to_pos2()).length();
+            for pos in &members {
+                assert!(((*pos - center.to_pos2()).length() - radius).abs() < 0.01);
+            }
+            spans.push((center.x - radius, center.x + radius));
+        }
+
+        // namespaces are side by side in name order
+        assert!(spans.windows(2).all(|pair| pair[0].1 < pair[1].0));
+    }
+
+    #[test]
+    fn force_directed_is_deterministic() {
+        let first = laid_out(Layout::ForceDirected, &fixture::graph());
+        let second = laid_out(Layout::ForceDirected, &fixture::graph());
+        assert_eq!(first, second);
+
+        let positions: Vec<Pos2> = first.values().copied().collect();
+        for (i, a) in positions.iter().enumerate() {
+            assert!(a.x.is_finite() && a.y.is_finite());
+            assert!(positions[i + 1..].iter().all(|b| a.distance(*b) > 1.));
+        }
+    }
+
+    #[test]
+    fn force_directed_falls_back_to_layers() {
+        let mut full = StableGraph::new();
+        for i in 0..2500 {
+            full.add_node(node(&format!("User_{}", i), NodeKind::Entity));
+        }
+
+        assert_eq!(
+            laid_out(Layout::ForceDirected, &full),
+            laid_out(Layout::Layered, &full)
+        );
+    }
+}
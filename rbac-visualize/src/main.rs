@@ -1,28 +1,285 @@
+use check::CheckPanel;
+use clap::Parser;
+use edge::{EdgeData, EdgeShapeFlex};
+use edit::EditPanel;
 use eframe::{run_native, App, CreationContext};
-use egui::{CentralPanel, Context};
-use egui_graphs::{DefaultEdgeShape, Graph, GraphView, SettingsInteraction, SettingsNavigation};
-use node::NodeShapeFlex;
+use egui::{CentralPanel, ComboBox, Context, ScrollArea, SidePanel, TopBottomPanel, Window};
+use egui_graphs::{Graph, GraphView, SettingsInteraction, SettingsNavigation};
+use export::Format;
+use filter::Filter;
+use inspector::Inspector;
+use layout::Layout;
+use node::{NodeKind, NodeShapeFlex};
 use petgraph::{
     stable_graph::{DefaultIx, EdgeIndex, NodeIndex, StableGraph},
     Directed,
 };
+use rbac::{decode_key_part, RBACError, RBAC, ROLE_TO_ENTITY};
+use schema_view::SchemaView;
+use store::Store;
 
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
+};
+mod check;
+mod edge;
+mod edit;
+mod export;
+mod filter;
+#[cfg(test)]
+mod fixture;
+mod inspector;
+mod layout;
 mod node;
+mod schema_view;
+mod store;
+mod style;
+mod sync;
+
+pub type ViewGraph =
+    Graph<node::NodeData, EdgeData, Directed, DefaultIx, NodeShapeFlex, EdgeShapeFlex>;
+
+/// Browse the graph of an rbac store.
+///
+/// The store is read as a rocksdb secondary and copied to the temp dir, so the viewer never
+/// writes to it unless it's opened with `--edit`, and works while another process has it open. With `--export` the graph is written to a file instead, without opening a
+/// window.
+#[derive(Parser)]
+#[command(name = "rbac-visualize")]
+struct Args {
+    /// path to the rocksdb datastore, asked for in the app when not given
+    #[arg(long)]
+    db: Option<PathBuf>,
+
+    /// json schema describing namespaces and roles
+    #[arg(long)]
+    schema: Option<PathBuf>,
+
+    /// check the store for changes every so many seconds and refresh the graph
+    #[arg(long)]
+    poll: Option<u64>,
+
+    /// open the store itself to grant and revoke roles, fails while another process has it open
+    #[arg(long, conflicts_with = "export")]
+    edit: bool,
+
+    /// how the vertices are laid out
+    #[arg(long, value_enum, default_value_t)]
+    layout: Layout,
+
+    /// write the graph to this file and exit, e.g. `graph.svg`
+    #[arg(long, requires = "db")]
+    export: Option<PathBuf>,
+
+    /// format of the export, guessed from the file's extension when not given
+    #[arg(long, value_enum, requires = "export")]
+    format: Option<Format>,
+}
+
+// how often the store is checked for changes when polling is turned on in the app
+const DEFAULT_POLL: Duration = Duration::from_secs(2);
+
+// frames the view is fitted to the graph for after a layout, the graph's bounds are those of
+// the frame drawn before
+const FIT_FRAMES: u8 = 2;
+
+// "open database" window
+#[derive(Default)]
+struct OpenDialog {
+    open: bool,
+    db: String,
+    schema: String,
+    // open the store itself instead of a copy
+    edit: bool,
+    error: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    // vertices and edges of the store
+    Instances,
+    // namespaces and roles of the schema
+    Schema,
+}
 
 pub struct FlexNodesApp {
-    g: Graph<node::NodeData, (), Directed, DefaultIx, NodeShapeFlex, DefaultEdgeShape>,
+    // every vertex and edge of the store, `g` shows the part passing `filter`
+    full: StableGraph<node::NodeData, EdgeData>,
+    g: ViewGraph,
     selected_node: Option<NodeIndex>,
     selected_edge: Option<EdgeIndex>,
+    store: Option<Store>,
+    dialog: OpenDialog,
+    polling: bool,
+    poll_interval: Duration,
+    last_poll: Instant,
+    // last refresh failure, cleared by the next successful one
+    refresh_error: Option<String>,
+    check: CheckPanel,
+    inspector: Inspector,
+    edit: EditPanel,
+    // of the store's vertices, for the legend and the filter
+    namespaces: Vec<String>,
+    filter: Filter,
+    layout: Layout,
+    fit_frames: u8,
+    tab: Tab,
+    schema_view: SchemaView,
 }
 
 impl FlexNodesApp {
-    fn new(_: &CreationContext<'_>) -> Self {
-        let g = generate_graph();
-        Self {
-            g: Graph::from(&g),
+    fn new(_: &CreationContext<'_>, args: Args) -> Self {
+        let mut app = Self {
+            full: StableGraph::new(),
+            g: Graph::from(&StableGraph::new()),
             selected_node: Option::default(),
             selected_edge: Option::default(),
+            store: None,
+            dialog: OpenDialog {
+                open: true,
+                db: path_string(args.db.as_ref()),
+                schema: path_string(args.schema.as_ref()),
+                edit: args.edit,
+                error: None,
+            },
+            polling: args.poll.is_some(),
+            poll_interval: args.poll.map_or(DEFAULT_POLL, Duration::from_secs),
+            last_poll: Instant::now(),
+            refresh_error: None,
+            check: CheckPanel::default(),
+            inspector: Inspector::default(),
+            edit: EditPanel::default(),
+            namespaces: Vec::new(),
+            filter: Filter::default(),
+            layout: args.layout,
+            fit_frames: 0,
+            tab: Tab::Instances,
+            schema_view: SchemaView::default(),
+        };
+
+        if args.db.is_some() {
+            app.open_store();
+        }
+
+        app
+    }
+
+    // opens the store named in the dialog, keeping the dialog up with the error on failure
+    fn open_store(&mut self) {
+        let schema = Some(self.dialog.schema.trim())
+            .filter(|schema| !schema.is_empty())
+            .map(PathBuf::from);
+
+        let opened = Store::open(
+            self.dialog.db.trim().as_ref(),
+            schema.as_deref(),
+            self.dialog.edit,
+        )
+        .map_err(|e| format!("{:?}", e))
+        .and_then(|store| {
+            let g = generate_graph(&store.rbac).map_err(|e| format!("{:?}", e))?;
+            Ok((store, g))
+        });
+
+        match opened {
+            Ok((store, g)) => {
+                self.schema_view = SchemaView::new(store.schema.as_ref());
+                self.filter = Filter::default();
+                self.g = Graph::from(&self.filter.apply(&g));
+                self.namespaces = namespaces(&g);
+                self.full = g;
+                self.lay_out();
+                self.selected_node = None;
+                self.selected_edge = None;
+                self.store = Some(store);
+                self.dialog.open = false;
+                self.dialog.error = None;
+                self.refresh_error = None;
+                self.check.clear();
+                self.edit.clear();
+                self.inspector.invalidate();
+            }
+            Err(e) => {
+                self.dialog.open = true;
+                self.dialog.error = Some(e);
+            }
+        }
+    }
+
+    // reads the store again and updates the graph in place
+    fn refresh(&mut self) {
+        let store = match &mut self.store {
+            Some(store) => store,
+            None => return,
+        };
+
+        let fresh = store
+            .refresh()
+            .map_err(|e| format!("{:?}", e))
+            .and_then(|_| generate_graph(&store.rbac).map_err(|e| format!("{:?}", e)));
+
+        match fresh {
+            Ok(fresh) => {
+                self.namespaces = namespaces(&fresh);
+                self.full = fresh;
+                self.apply_filter();
+                self.inspector.invalidate();
+                self.refresh_error = None;
+            }
+            Err(e) => self.refresh_error = Some(e),
+        }
+    }
+
+    // updates the view in place to show the part of `full` passing the filter
+    fn apply_filter(&mut self) {
+        let removed = sync::sync_graph(&mut self.g, &self.filter.apply(&self.full));
+        if self
+            .selected_node
+            .is_some_and(|idx| removed.nodes.contains(&idx))
+        {
+            self.selected_node = None;
+        }
+        if self
+            .selected_edge
+            .is_some_and(|idx| removed.edges.contains(&idx))
+        {
+            self.selected_edge = None;
+        }
+
+        // vertices and edges shown again on the path are highlighted too
+        check::highlight_path(&mut self.g, self.check.path());
+    }
+
+    // moves the shown vertices to their place in the chosen layout and fits them in the view
+    fn lay_out(&mut self) {
+        self.layout.apply(&mut self.g);
+        self.fit_frames = FIT_FRAMES;
+    }
+
+    // refreshes when the store changed since it was last read, at most once per interval
+    fn poll(&mut self, ctx: &Context) {
+        if !self.polling {
+            return;
+        }
+
+        // keep polling while nothing else causes a repaint
+        ctx.request_repaint_after(self.poll_interval);
+        if self.last_poll.elapsed() < self.poll_interval {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        let changed = match &self.store {
+            Some(store) => store.changed(),
+            None => return,
+        };
+        match changed {
+            Ok(true) => self.refresh(),
+            Ok(false) => {}
+            Err(e) => self.refresh_error = Some(format!("{:?}", e)),
         }
     }
 
@@ -39,10 +296,230 @@ impl FlexNodesApp {
         }
     }
 
+    fn render_menu(&mut self, ctx: &Context) {
+        TopBottomPanel::top("menu").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Open database…").clicked() {
+                    self.dialog.open = true;
+                }
+
+                match &self.store {
+                    Some(store) if store.writable => {
+                        ui.label(format!("{} (editing)", store.path.display()));
+                    }
+                    Some(store) => {
+                        ui.label(format!("{} (read-only copy)", store.path.display()));
+                    }
+                    None => {
+                        ui.label("no database");
+                        return;
+                    }
+                }
+
+                ui.separator();
+                ui.selectable_value(&mut self.tab, Tab::Instances, "Instances");
+                ui.selectable_value(&mut self.tab, Tab::Schema, "Schema");
+
+                ui.separator();
+                if ui.button("Refresh").clicked() {
+                    self.refresh();
+                }
+                ui.checkbox(
+                    &mut self.polling,
+                    format!("auto refresh ({}s)", self.poll_interval.as_secs()),
+                );
+
+                ui.separator();
+                let chosen = self.layout;
+                ComboBox::from_label("layout")
+                    .selected_text(self.layout.name())
+                    .show_ui(ui, |ui| {
+                        for layout in Layout::ALL {
+                            ui.selectable_value(&mut self.layout, layout, layout.name());
+                        }
+                    });
+                if ui.button("Lay out again").clicked() || self.layout != chosen {
+                    self.lay_out();
+                }
+
+                if let Some(error) = &self.refresh_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+            });
+        });
+    }
+
+    fn render_dialog(&mut self, ctx: &Context) {
+        let mut open = self.dialog.open;
+        let mut submitted = false;
+
+        Window::new("Open database")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("rocksdb datastore");
+                ui.text_edit_singleline(&mut self.dialog.db);
+                ui.label("schema (optional)");
+                ui.text_edit_singleline(&mut self.dialog.schema);
+                ui.checkbox(&mut self.dialog.edit, "open for editing")
+                    .on_hover_text(
+                        "writes go to the store itself, which no other process may have open",
+                    );
+
+                if let Some(error) = &self.dialog.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                submitted = ui.button("Open").clicked();
+            });
+
+        self.dialog.open = open;
+        if submitted {
+            self.open_store();
+        }
+    }
+
+    fn render_check(&mut self, ctx: &Context) {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return,
+        };
+
+        let selected = self
+            .selected_node
+            .and_then(|idx| self.g.node(idx))
+            .map(|n| n.payload().entity.clone());
+
+        let (checked, wrote, action) = SidePanel::left("check")
+            .show(ctx, |ui| {
+                ScrollArea::vertical()
+                    .show(ui, |ui| {
+                        let checked = self.check.show(ui, &store.rbac, store.schema.as_ref());
+
+                        ui.separator();
+                        let wrote = self.edit.show(ui, store, &self.full);
+
+                        ui.separator();
+                        let action =
+                            self.filter
+                                .show(ui, &self.full, &self.namespaces, selected.as_deref());
+
+                        ui.separator();
+                        ui.collapsing("Legend", |ui| style::legend(ui, &self.namespaces));
+
+                        (checked, wrote, action)
+                    })
+                    .inner
+            })
+            .inner;
+
+        if wrote {
+            self.refresh();
+        }
+        if let Some(found) = &action.found {
+            self.filter.reveal(found, &self.full);
+        }
+        if action.changed || action.found.is_some() {
+            self.apply_filter();
+        } else if checked {
+            check::highlight_path(&mut self.g, self.check.path());
+        }
+
+        let found = action.found.and_then(|found| {
+            self.g
+                .nodes_iter()
+                .find(|(_, n)| n.payload().entity == found)
+        });
+        if let Some((idx, _)) = found {
+            self.select_node(idx);
+        }
+    }
+
+    fn render_inspector(&mut self, ctx: &Context) {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return,
+        };
+
+        let clicked = SidePanel::right("inspector")
+            .show(ctx, |ui| {
+                let clicked = self.inspector.show(
+                    ui,
+                    &self.g,
+                    &store.rbac,
+                    self.selected_node,
+                    self.selected_edge,
+                );
+
+                // the inspector shows the selected vertex over the selected edge
+                if self.selected_node.is_none() {
+                    self.edit.show_edge(ui, &self.g, store, self.selected_edge);
+                }
+
+                clicked
+            })
+            .inner;
+        let wrote = self.edit.confirm(ctx, store);
+
+        if let Some(idx) = clicked {
+            self.select_node(idx);
+        }
+        if wrote {
+            self.refresh();
+        }
+    }
+
+    // selects `idx` in the graph as if it had been clicked
+    fn select_node(&mut self, idx: NodeIndex) {
+        let nodes: Vec<_> = self.g.nodes_iter().map(|(idx, _)| idx).collect();
+        for n in nodes {
+            if let Some(node) = self.g.node_mut(n) {
+                node.set_selected(n == idx);
+            }
+        }
+        let edges: Vec<_> = self.g.edges_iter().map(|(e, _)| e).collect();
+        for e in edges {
+            if let Some(edge) = self.g.edge_mut(e) {
+                edge.set_selected(false);
+            }
+        }
+
+        self.g.set_selected_nodes(vec![idx]);
+        self.g.set_selected_edges(Vec::new());
+        self.selected_node = Some(idx);
+        self.selected_edge = None;
+    }
+
     fn render(&mut self, ctx: &Context) {
+        self.render_menu(ctx);
+        self.render_dialog(ctx);
+
+        if self.tab == Tab::Schema {
+            // entities per namespace, roles aren't counted
+            let mut counts = HashMap::new();
+            for n in self.full.node_weights() {
+                if n.kind != NodeKind::Role {
+                    *counts.entry(n.namespace.clone()).or_insert(0) += 1;
+                }
+            }
+
+            CentralPanel::default().show(ctx, |ui| self.schema_view.show(ui, &counts));
+            return;
+        }
+
+        self.render_check(ctx);
+        self.render_inspector(ctx);
+
+        let fit = self.fit_frames > 0;
+        if fit {
+            self.fit_frames -= 1;
+            ctx.request_repaint();
+        }
+
         CentralPanel::default().show(ctx, |ui| {
             let widget =
-                &mut GraphView::<_, _, _, _, NodeShapeFlex, DefaultEdgeShape>::new(&mut self.g)
+                &mut GraphView::<_, _, _, _, NodeShapeFlex, EdgeShapeFlex>::new(&mut self.g)
                     .with_interactions(
                         &SettingsInteraction::default()
                             .with_dragging_enabled(true)
@@ -51,7 +528,7 @@ impl FlexNodesApp {
                     )
                     .with_navigations(
                         &SettingsNavigation::default()
-                            .with_fit_to_screen_enabled(false)
+                            .with_fit_to_screen_enabled(fit)
                             .with_zoom_and_pan_enabled(true),
                     );
             ui.add(widget);
@@ -61,26 +538,53 @@ impl FlexNodesApp {
 
 impl App for FlexNodesApp {
     fn update(&mut self, ctx: &Context, _: &mut eframe::Frame) {
+        self.poll(ctx);
         self.read_data();
         self.render(ctx);
     }
 }
 
-fn generate_graph() -> StableGraph<node::NodeData, ()> {
+fn generate_graph(rbac: &RBAC) -> Result<StableGraph<node::NodeData, EdgeData>, RBACError> {
     let mut g = StableGraph::new();
 
-    use rbac::RBAC;
-
-    let rbac = RBAC::new("./rbac-example/rocksdb/test2");
-
-    let nodes = rbac.get_all_vertices().unwrap();
-    let edges = rbac.get_all_edges().unwrap();
+    let nodes = rbac.get_all_vertices()?;
+    let edges = rbac.get_all_edges()?;
 
     let mut node_index_map = HashMap::new();
 
+    // role vertices point at their object with `role_to_entity`
+    let roles: HashSet<_> = edges
+        .iter()
+        .filter(|e| e.t.as_str() == ROLE_TO_ENTITY)
+        .map(|e| e.outbound_id)
+        .collect();
+    let objects: HashSet<_> = edges
+        .iter()
+        .filter(|e| e.t.as_str() == ROLE_TO_ENTITY)
+        .map(|e| e.inbound_id)
+        .collect();
+
     for node in nodes {
+        let kind = if roles.contains(&node.id) {
+            NodeKind::Role
+        } else if objects.contains(&node.id) {
+            NodeKind::Object
+        } else {
+            NodeKind::Entity
+        };
+
+        let entity = node.t.to_string();
+        let namespace = entity
+            .split('_')
+            .next()
+            .and_then(decode_key_part)
+            .unwrap_or_default();
+
         let idx = g.add_node(node::NodeData {
-            entity: node.t.to_string(),
+            entity,
+            namespace,
+            kind,
+            highlighted: false,
         });
         node_index_map.insert(node.id, idx);
     }
@@ -88,18 +592,60 @@ fn generate_graph() -> StableGraph<node::NodeData, ()> {
     for edge in edges {
         let a = node_index_map.get(&edge.outbound_id).unwrap();
         let b = node_index_map.get(&edge.inbound_id).unwrap();
-        g.add_edge(*a, *b, ());
+        g.add_edge(
+            *a,
+            *b,
+            EdgeData {
+                t: edge.t.to_string(),
+                highlighted: false,
+            },
+        );
     }
 
-    g
+    Ok(g)
+}
+
+fn namespaces(g: &StableGraph<node::NodeData, EdgeData>) -> Vec<String> {
+    let namespaces: BTreeSet<&str> = g.node_weights().map(|n| n.namespace.as_str()).collect();
+    namespaces.into_iter().map(str::to_string).collect()
+}
+
+fn path_string(path: Option<&PathBuf>) -> String {
+    path.map(|path| path.display().to_string())
+        .unwrap_or_default()
+}
+
+// lays out the whole store as `--layout` says and writes it as `--export` says
+fn export_store(args: &Args, db: &Path, path: &Path) -> Result<(), String> {
+    let format = args
+        .format
+        .or_else(|| Format::from_path(path))
+        .ok_or("can't tell the format from the file name, use --format")?;
+
+    let store = Store::open(db, args.schema.as_deref(), false).map_err(|e| format!("{:?}", e))?;
+    let mut g: ViewGraph =
+        Graph::from(&generate_graph(&store.rbac).map_err(|e| format!("{:?}", e))?);
+    args.layout.apply(&mut g);
+
+    export::export(&g, format, path).map_err(|e| format!("{:?}", e))
 }
 
 fn main() {
+    let args = Args::parse();
+
+    if let (Some(db), Some(path)) = (&args.db, &args.export) {
+        if let Err(e) = export_store(&args, db, path) {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+        return;
+    }
+
     let native_options = eframe::NativeOptions::default();
     run_native(
         "rbac-visualize",
         native_options,
-        Box::new(|cc| Ok(Box::new(FlexNodesApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(FlexNodesApp::new(cc, args)))),
     )
     .unwrap();
 }
@@ -2,15 +2,55 @@ use egui::{epaint::TextShape, Color32, FontFamily, FontId, Pos2, Rect, Shape, St
 use egui_graphs::{DisplayNode, NodeProps};
 use petgraph::{stable_graph::IndexType, EdgeType};
 
+use crate::style::{namespace_color, node_stroke_width};
+
+// color of the vertices and edges on the path of a granted check
+pub const HIGHLIGHT: Color32 = Color32::from_rgb(0x2e, 0xa0, 0x43);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    // an entity no role is defined on, e.g. a user
+    Entity,
+    // an entity with role vertices, e.g. a post or a group
+    Object,
+    Role,
+}
+
+impl NodeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeKind::Entity => "entity",
+            NodeKind::Object => "object",
+            NodeKind::Role => "role",
+        }
+    }
+
+    // how it looks in the legend
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            NodeKind::Entity => "▭",
+            NodeKind::Object => "▬",
+            NodeKind::Role => "⬡",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct NodeData {
     pub entity: String,
+    pub namespace: String,
+    pub kind: NodeKind,
+    // on the path of the last check
+    pub highlighted: bool,
 }
 
 #[derive(Clone)]
 pub struct NodeShapeFlex {
     label: String,
     loc: Pos2,
+    namespace: String,
+    kind: NodeKind,
+    highlighted: bool,
 
     size_x: f32,
     size_y: f32,
@@ -21,6 +61,9 @@ impl From<NodeProps<NodeData>> for NodeShapeFlex {
         Self {
             label: node_props.payload.entity,
             loc: node_props.location,
+            namespace: node_props.payload.namespace,
+            kind: node_props.payload.kind,
+            highlighted: node_props.payload.highlighted,
 
             size_x: 0.,
             size_y: 0.,
@@ -42,7 +85,12 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType> DisplayNode<NodeData, E, Ty, Ix> for
     fn shapes(&mut self, ctx: &egui_graphs::DrawContext) -> Vec<egui::Shape> {
         // find node center location on the screen coordinates
         let center = ctx.meta.canvas_to_screen_pos(self.loc);
-        let color = ctx.ctx.style().visuals.text_color();
+        let color = if self.highlighted {
+            HIGHLIGHT
+        } else {
+            ctx.ctx.style().visuals.text_color()
+        };
+        let namespace = namespace_color(&self.namespace);
 
         // create label
         let galley = ctx.ctx.fonts(|f| {
@@ -59,9 +107,23 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType> DisplayNode<NodeData, E, Ty, Ix> for
         // create the shape and add it to the layers
         let shape_label = TextShape::new(center + offset, galley, color);
 
+        // entities are outlined in their namespace's color, objects also filled with it and
+        // roles cut at the corners
         let rect = shape_label.visual_bounding_rect();
-        let points = rect_to_points(rect, 5.);
-        let shape_rect = Shape::convex_polygon(points, Color32::default(), Stroke::new(1., color));
+        let points = match self.kind {
+            NodeKind::Role => rect_to_chamfered_points(rect, 5.),
+            NodeKind::Entity | NodeKind::Object => rect_to_points(rect, 5.),
+        };
+        let fill = match self.kind {
+            NodeKind::Object => namespace.gamma_multiply(0.35),
+            NodeKind::Entity | NodeKind::Role => Color32::default(),
+        };
+        let stroke = if self.highlighted {
+            Stroke::new(3., HIGHLIGHT)
+        } else {
+            Stroke::new(node_stroke_width(self.kind), namespace)
+        };
+        let shape_rect = Shape::convex_polygon(points, fill, stroke);
 
         // update self size
         self.size_x = rect.size().x;
@@ -73,10 +135,13 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType> DisplayNode<NodeData, E, Ty, Ix> for
     fn update(&mut self, state: &NodeProps<NodeData>) {
         self.label = state.payload.entity.clone();
         self.loc = state.location;
+        self.namespace = state.payload.namespace.clone();
+        self.kind = state.payload.kind;
+        self.highlighted = state.payload.highlighted;
     }
 }
 
-fn find_intersection(center: Pos2, size_x: f32, size_y: f32, direction: Vec2) -> Pos2 {
+pub fn find_intersection(center: Pos2, size_x: f32, size_y: f32, direction: Vec2) -> Pos2 {
     if (direction.x.abs() * size_y) > (direction.y.abs() * size_x) {
         // intersects left or right side
         let x = if direction.x > 0.0 {
@@ -98,7 +163,7 @@ fn find_intersection(center: Pos2, size_x: f32, size_y: f32, direction: Vec2) ->
     }
 }
 
-fn rect_to_points(rect: Rect, padding: f32) -> Vec<Pos2> {
+pub fn rect_to_points(rect: Rect, padding: f32) -> Vec<Pos2> {
     let top_left = rect.min - Vec2::new(padding, padding);
     let bottom_right = rect.max + Vec2::new(padding, padding);
     let top_right = Pos2::new(bottom_right.x, top_left.y);
@@ -106,3 +171,21 @@ fn rect_to_points(rect: Rect, padding: f32) -> Vec<Pos2> {
 
     vec![top_left, top_right, bottom_right, bottom_left]
 }
+
+// like `rect_to_points`, with the corners cut off
+pub fn rect_to_chamfered_points(rect: Rect, padding: f32) -> Vec<Pos2> {
+    let min = rect.min - Vec2::new(padding, padding);
+    let max = rect.max + Vec2::new(padding, padding);
+    let cut = padding.min((max.y - min.y) / 2.);
+
+    vec![
+        Pos2::new(min.x + cut, min.y),
+        Pos2::new(max.x - cut, min.y),
+        Pos2::new(max.x, min.y + cut),
+        Pos2::new(max.x, max.y - cut),
+        Pos2::new(max.x - cut, max.y),
+        Pos2::new(min.x + cut, max.y),
+        Pos2::new(min.x, max.y - cut),
+        Pos2::new(min.x, min.y + cut),
+    ]
+}
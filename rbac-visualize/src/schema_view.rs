@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use egui::{Frame, RichText, ScrollArea, Ui};
+use rbac::{
+    decode_key_part, schema::Schema, NamespaceRole, NamespaceToString, RoleHierarchy, ToNode,
+};
+
+use crate::style::namespace_color;
+
+// "schema" tab showing the policy model: namespaces, their roles and what implies what. It's
+// read through `NamespaceRole` and `RoleHierarchy` like the store materializes it, so it shows
+// what `RBAC` writes rather than how the schema file spells it.
+#[derive(Default)]
+pub struct SchemaView {
+    namespaces: Vec<NamespaceModel>,
+    // whether the store was opened with a schema
+    loaded: bool,
+}
+
+struct NamespaceModel {
+    name: String,
+    // in declaration order, with whether holders act as the entity
+    roles: Vec<(String, bool)>,
+    // (parent, child), holders of the parent hold the child too
+    hierarchy: Vec<(String, String)>,
+    // (role, `Namespace#role` implying it, via)
+    implied: Vec<(String, String, String)>,
+}
+
+impl SchemaView {
+    pub fn new(schema: Option<&Schema>) -> Self {
+        let schema = match schema {
+            Some(schema) => schema,
+            None => return SchemaView::default(),
+        };
+
+        let namespaces = schema
+            .namespaces
+            .iter()
+            .filter_map(|def| {
+                // any entity of the namespace leads to its roles
+                let node = schema.entity(&def.name, "").ok()?.to_node(None);
+                let mut namespace = NamespaceModel {
+                    name: def.name.clone(),
+                    roles: Vec::new(),
+                    hierarchy: Vec::new(),
+                    implied: Vec::new(),
+                };
+
+                if let Some(roles) = node.namespace().get_roles() {
+                    roles.iter_all(&mut |role| {
+                        namespace
+                            .roles
+                            .push((role_name(&*role), role.is_membership()));
+                    });
+                    roles.iter_hierarchy(&mut |parent, child| {
+                        namespace
+                            .hierarchy
+                            .push((role_name(&*parent), role_name(&*child)));
+                    });
+                    roles.iter_implied(&mut |role, implied_by, via| {
+                        let implied_by = format!(
+                            "{}#{}",
+                            decode_key_part(&implied_by.to_node(None).namespace().to_string())
+                                .unwrap_or_default(),
+                            role_name(&*implied_by)
+                        );
+                        namespace
+                            .implied
+                            .push((role_name(&*role), implied_by, role_name(&*via)));
+                    });
+                }
+
+                Some(namespace)
+            })
+            .collect();
+
+        SchemaView {
+            namespaces,
+            loaded: true,
+        }
+    }
+
+    // one card per namespace, `counts` being the number of its entities in the store
+    pub fn show(&self, ui: &mut Ui, counts: &HashMap<String, usize>) {
+        if !self.loaded {
+            ui.label("open the database with a schema to see its model");
+            return;
+        }
+
+        ui.label("roles imply the ones nested under them");
+        ScrollArea::both().show(ui, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for namespace in &self.namespaces {
+                    Frame::group(ui.style()).show(ui, |ui| {
+                        ui.vertical(|ui| namespace.show(ui, counts));
+                    });
+                }
+            });
+        });
+    }
+}
+
+impl NamespaceModel {
+    fn show(&self, ui: &mut Ui, counts: &HashMap<String, usize>) {
+        ui.label(
+            RichText::new(&self.name)
+                .strong()
+                .color(namespace_color(&self.name)),
+        );
+        ui.label(format!(
+            "{} in the store",
+            counts.get(&self.name).copied().unwrap_or(0)
+        ));
+
+        if self.roles.is_empty() {
+            ui.label("no roles");
+            return;
+        }
+
+        ui.separator();
+        for (role, _) in &self.roles {
+            // roots of the hierarchy, the rest is nested under them
+            if self.hierarchy.iter().all(|(_, child)| child != role) {
+                self.show_role(ui, role, &mut vec![role.as_str()]);
+            }
+        }
+
+        if !self.implied.is_empty() {
+            ui.separator();
+            ui.strong("implied");
+            for (role, implied_by, via) in &self.implied {
+                ui.label(format!("{} ⇐ {} via {}", role, implied_by, via));
+            }
+        }
+    }
+
+    // `path` holds the roles above, so a cycle in the hierarchy ends instead of recursing
+    fn show_role<'a>(&'a self, ui: &mut Ui, role: &str, path: &mut Vec<&'a str>) {
+        let membership = self
+            .roles
+            .iter()
+            .any(|(name, membership)| name == role && *membership);
+        if membership {
+            ui.label(format!("{} (membership)", role));
+        } else {
+            ui.label(role);
+        }
+
+        let nested: Vec<&str> = self
+            .hierarchy
+            .iter()
+            .filter(|(parent, child)| parent == role && !path.contains(&child.as_str()))
+            .map(|(_, child)| child.as_str())
+            .collect();
+        if nested.is_empty() {
+            return;
+        }
+        ui.indent(role, |ui| {
+            for child in nested {
+                path.push(child);
+                self.show_role(ui, child, path);
+                path.pop();
+            }
+        });
+    }
+}
+
+fn role_name(role: &dyn RoleHierarchy) -> String {
+    role.to_node(None).id().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn schema_view_model() {
+        let view = SchemaView::new(None);
+        assert!(!view.loaded);
+        assert!(view.namespaces.is_empty());
+
+        let schema = fixture::schema();
+        let view = SchemaView::new(Some(&schema));
+        assert!(view.loaded);
+        let names: Vec<&str> = view.namespaces.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["User", "Post", "Group"]);
+
+        let user = &view.namespaces[0];
+        assert!(user.roles.is_empty() && user.hierarchy.is_empty() && user.implied.is_empty());
+
+        let owned = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(a, b)| (a.to_string(), b.to_string()))
+                .collect()
+        };
+        let post = &view.namespaces[1];
+        assert_eq!(
+            post.roles,
+            [
+                ("Writer".to_string(), false),
+                ("Viewer".to_string(), false),
+                ("OwnedBy".to_string(), false)
+            ]
+        );
+        assert_eq!(post.hierarchy, owned(&[("Writer", "Viewer")]));
+        assert_eq!(
+            post.implied,
+            [(
+                "Writer".to_string(),
+                "Group#Admin".to_string(),
+                "OwnedBy".to_string()
+            )]
+        );
+
+        // membership is flagged on the role declaring it
+        let group = &view.namespaces[2];
+        assert_eq!(
+            group.roles,
+            [("Admin".to_string(), false), ("Member".to_string(), true)]
+        );
+        assert_eq!(group.hierarchy, owned(&[("Admin", "Member")]));
+        assert!(group.implied.is_empty());
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use rbac::{
+    schema::{Schema, SchemaError},
+    RBACError, RBAC,
+};
+
+static VIEWS: AtomicUsize = AtomicUsize::new(0);
+
+// A store opened for viewing. `RBAC` only opens stores for writing, which takes rocksdb's lock
+// and would keep the process owning the store out, so the viewer opens it as a rocksdb
+// secondary instead: read only, without the lock, and alongside a writer. indradb can't read
+// through a secondary, so what it reads is copied into a database under the temp dir, which
+// the viewer opens. Stores opened for editing are opened in place instead, which fails while
+// another process has them open too.
+pub struct Store {
+    pub path: PathBuf,
+    pub rbac: RBAC,
+    pub schema: Option<Schema>,
+    pub writable: bool,
+    // of the store when it was last read
+    fingerprint: Vec<FileState>,
+    // dropped after `rbac`, which has its copy open. None when opened for editing
+    view: Option<View>,
+}
+
+// name, size and modification time of a file of the store. rocksdb appends to its log and adds
+// files on every write, so a store that changed has a different list.
+type FileState = (PathBuf, u64, Option<SystemTime>);
+
+// the secondary reading a store and the copies made of it, all under `dir`
+struct View {
+    secondary: rocksdb::DB,
+    // column families of the store
+    names: Vec<String>,
+    // number of copies made, the last one is at `copy_path(copies)`
+    copies: usize,
+    // removed after the secondary is closed
+    dir: TempDir,
+}
+
+// directory under the temp dir, removed on drop
+struct TempDir(PathBuf);
+
+#[derive(Debug)]
+pub enum StoreError {
+    NotFound(PathBuf),
+    Io(io::Error),
+    Rbac(RBACError),
+    Rocksdb(rocksdb::Error),
+    Schema(SchemaError),
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<RBACError> for StoreError {
+    fn from(e: RBACError) -> Self {
+        StoreError::Rbac(e)
+    }
+}
+
+impl From<rocksdb::Error> for StoreError {
+    fn from(e: rocksdb::Error) -> Self {
+        StoreError::Rocksdb(e)
+    }
+}
+
+impl From<SchemaError> for StoreError {
+    fn from(e: SchemaError) -> Self {
+        StoreError::Schema(e)
+    }
+}
+
+impl Store {
+    pub fn open(path: &Path, schema: Option<&Path>, writable: bool) -> Result<Store, StoreError> {
+        // rocksdb would create an empty store at a mistyped path
+        if !path.join("CURRENT").is_file() {
+            return Err(StoreError::NotFound(path.to_path_buf()));
+        }
+
+        let schema = schema.map(Schema::load).transpose()?;
+        let fingerprint = fingerprint(path)?;
+        let (rbac, view) = if writable {
+            (RBAC::open(&path.to_string_lossy())?, None)
+        } else {
+            let (rbac, view) = View::open(path)?;
+            (rbac, Some(view))
+        };
+
+        Ok(Store {
+            path: path.to_path_buf(),
+            rbac,
+            schema,
+            writable,
+            fingerprint,
+            view,
+        })
+    }
+
+    // whether the store was written to since it was last read
+    pub fn changed(&self) -> Result<bool, StoreError> {
+        Ok(fingerprint(&self.path)? != self.fingerprint)
+    }
+
+    // catches up with the writes made since the store was last read and copies it again, a
+    // store opened for editing is read as it is
+    pub fn refresh(&mut self) -> Result<(), StoreError> {
+        let fingerprint = fingerprint(&self.path)?;
+        if let Some(view) = &mut self.view {
+            view.secondary.try_catch_up_with_primary()?;
+            let rbac = view.copy()?;
+
+            // the old copy is closed before it's removed
+            self.rbac = rbac;
+            let _ = fs::remove_dir_all(view.copy_path(view.copies - 1));
+        }
+        self.fingerprint = fingerprint;
+        Ok(())
+    }
+}
+
+impl View {
+    fn open(path: &Path) -> Result<(RBAC, View), StoreError> {
+        let dir = TempDir(std::env::temp_dir().join(format!(
+            "rbac-visualize-{}-{}",
+            process::id(),
+            VIEWS.fetch_add(1, Ordering::SeqCst)
+        )));
+        fs::create_dir_all(&dir.0)?;
+
+        // a secondary has to keep every table file open, the writer may compact them away
+        let mut opts = rocksdb::Options::default();
+        opts.set_max_open_files(-1);
+        let names = rocksdb::DB::list_cf(&opts, path)?;
+        let secondary = rocksdb::DB::open_cf_as_secondary(
+            &opts,
+            path,
+            dir.0.join("secondary").as_path(),
+            &names,
+        )?;
+
+        let mut view = View {
+            secondary,
+            names,
+            copies: 0,
+            dir,
+        };
+        let rbac = view.copy()?;
+
+        Ok((rbac, view))
+    }
+
+    // copies what the secondary has read into a new database and opens it
+    fn copy(&mut self) -> Result<RBAC, StoreError> {
+        self.copies += 1;
+        let path = self.copy_path(self.copies);
+
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let copy = rocksdb::DB::open_cf(&opts, &path, &self.names)?;
+
+        for name in &self.names {
+            let (from, to) = match (self.secondary.cf_handle(name), copy.cf_handle(name)) {
+                (Some(from), Some(to)) => (from, to),
+                _ => continue,
+            };
+
+            let mut batch = rocksdb::WriteBatch::default();
+            for entry in self
+                .secondary
+                .full_iterator_cf(from, rocksdb::IteratorMode::Start)
+            {
+                let (key, value) = entry?;
+                batch.put_cf(to, key, value);
+                // keeps large stores out of memory
+                if batch.len() >= 10_000 {
+                    copy.write(std::mem::take(&mut batch))?;
+                }
+            }
+            copy.write(batch)?;
+        }
+        drop(copy);
+
+        Ok(RBAC::open(&path.to_string_lossy())?)
+    }
+
+    fn copy_path(&self, n: usize) -> PathBuf {
+        self.dir.0.join(format!("copy-{}", n))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // nothing to do if it's already gone
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn fingerprint(path: &Path) -> io::Result<Vec<FileState>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        files.push((entry.path(), metadata.len(), metadata.modified().ok()));
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use rbac::EntityRelationship;
+
+    use super::*;
+    use crate::fixture;
+
+    fn relationship(schema: &Schema, tuple: &str) -> EntityRelationship {
+        let (subject, role, object) = schema.parse_relationship(tuple).unwrap();
+        EntityRelationship::new(&subject, &role, &object)
+    }
+
+    #[test]
+    fn refresh_while_written() {
+        let path = std::env::temp_dir().join(format!("rbac-visualize-store-{}", process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let schema = fixture::schema();
+
+        // the writer keeps the store open throughout
+        let writer = RBAC::open(&path.to_string_lossy()).unwrap();
+        let alice = relationship(&schema, "Post:1#Viewer@User:alice");
+        let bob = relationship(&schema, "Post:1#Viewer@User:bob");
+        writer.add_relationship(&alice).unwrap();
+
+        let mut store = Store::open(&path, None, false).unwrap();
+        assert_eq!(store.rbac.allowed(&alice).unwrap(), true);
+        assert!(matches!(
+            store.rbac.allowed(&bob),
+            Err(RBACError::VertexNotFound)
+        ));
+        assert_eq!(store.changed().unwrap(), false);
+
+        writer.add_relationship(&bob).unwrap();
+        writer.remove_relationship(&alice).unwrap();
+        assert_eq!(store.changed().unwrap(), true);
+
+        store.refresh().unwrap();
+        assert_eq!(store.rbac.allowed(&alice).unwrap(), false);
+        assert_eq!(store.rbac.allowed(&bob).unwrap(), true);
+        assert_eq!(store.changed().unwrap(), false);
+
+        // nothing was added next to the store, and the copies go with the viewer
+        let dir = store.view.as_ref().unwrap().dir.0.clone();
+        assert!(dir.starts_with(std::env::temp_dir()));
+        let entries: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().starts_with(".rbac-visualize"))
+            .collect();
+        assert!(entries.is_empty());
+        drop(store);
+        assert!(!dir.exists());
+
+        drop(writer);
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
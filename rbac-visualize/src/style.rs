@@ -0,0 +1,61 @@
+use egui::{ecolor::Hsva, Color32, Ui};
+use rbac::{ENTITY_TO_ROLE, INHERITS, ROLE_TO_ENTITY};
+
+use crate::node::{NodeKind, HIGHLIGHT};
+
+// Namespaces get a color of their own, picked from the name so it's the same in every session.
+pub fn namespace_color(namespace: &str) -> Color32 {
+    // fnv-1a
+    let hash = namespace
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    let hue = (hash % 360) as f32 / 360.;
+
+    Hsva::new(hue, 0.55, 0.85, 1.).into()
+}
+
+pub fn edge_color(t: &str) -> Color32 {
+    match t {
+        ENTITY_TO_ROLE => Color32::from_rgb(0x4a, 0x90, 0xd9),
+        INHERITS => Color32::from_rgb(0x9b, 0x59, 0xb6),
+        ROLE_TO_ENTITY => Color32::from_rgb(0xe6, 0x7e, 0x22),
+        _ => Color32::GRAY,
+    }
+}
+
+// `inherits` edges are drawn thinner, they only tie roles of one object together
+pub fn edge_width(t: &str) -> f32 {
+    match t {
+        INHERITS => 1.,
+        _ => 2.,
+    }
+}
+
+// objects are outlined thicker than the entities and roles
+pub fn node_stroke_width(kind: NodeKind) -> f32 {
+    match kind {
+        NodeKind::Object => 2.,
+        NodeKind::Entity | NodeKind::Role => 1.,
+    }
+}
+
+// colors and shapes used in the graph, for the namespaces it shows
+pub fn legend(ui: &mut Ui, namespaces: &[String]) {
+    ui.strong("namespaces");
+    for namespace in namespaces {
+        ui.colored_label(namespace_color(namespace), format!("■ {}", namespace));
+    }
+
+    ui.strong("vertices");
+    for kind in [NodeKind::Entity, NodeKind::Object, NodeKind::Role] {
+        ui.label(format!("{} {}", kind.symbol(), kind.name()));
+    }
+
+    ui.strong("edges");
+    for t in [ENTITY_TO_ROLE, INHERITS, ROLE_TO_ENTITY] {
+        ui.colored_label(edge_color(t), format!("→ {}", t));
+    }
+    ui.colored_label(HIGHLIGHT, "→ path of the last check");
+}
@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+
+use egui::Vec2;
+use petgraph::stable_graph::{EdgeIndex, NodeIndex, StableGraph};
+
+use crate::{edge::EdgeData, node::NodeData, ViewGraph};
+
+// where a new node goes relative to a neighbour that was already shown
+const SPAWN_OFFSET: Vec2 = Vec2::new(40., 40.);
+
+// what `sync_graph` took out of the view
+#[derive(Default)]
+pub struct Removed {
+    pub nodes: HashSet<NodeIndex>,
+    pub edges: HashSet<EdgeIndex>,
+}
+
+// Brings the view in line with `fresh` without rebuilding it: nodes are matched by entity key
+// and edges by their endpoints and type, so everything still in the store keeps its position, index and
+// selection. New nodes are placed next to a neighbour that was already shown.
+pub fn sync_graph(g: &mut ViewGraph, fresh: &StableGraph<NodeData, EdgeData>) -> Removed {
+    let mut removed = Removed::default();
+
+    let wanted: HashSet<&str> = fresh.node_weights().map(|n| n.entity.as_str()).collect();
+    let shown: Vec<(NodeIndex, String)> = g
+        .nodes_iter()
+        .map(|(idx, n)| (idx, n.payload().entity.clone()))
+        .collect();
+
+    let mut index = HashMap::new();
+    for (idx, entity) in shown {
+        if wanted.contains(entity.as_str()) {
+            index.insert(entity, idx);
+        } else {
+            // takes its edges with it
+            let edges: Vec<EdgeIndex> = g
+                .edges_iter()
+                .filter(|(e, _)| {
+                    g.edge_endpoints(*e)
+                        .is_some_and(|(a, b)| a == idx || b == idx)
+                })
+                .map(|(e, _)| e)
+                .collect();
+            removed.edges.extend(edges);
+
+            g.remove_node(idx);
+            removed.nodes.insert(idx);
+        }
+    }
+
+    // kept vertices take the new data, e.g. an entity becomes an object once a role is granted
+    // on it
+    let fresh_nodes: HashMap<&str, &NodeData> = fresh
+        .node_weights()
+        .map(|n| (n.entity.as_str(), n))
+        .collect();
+    for (entity, idx) in &index {
+        if let (Some(n), Some(data)) = (g.node_mut(*idx), fresh_nodes.get(entity.as_str())) {
+            *n.payload_mut() = (*data).clone();
+        }
+    }
+
+    let wanted_edges: HashSet<(&str, &str, &str)> = fresh
+        .edge_indices()
+        .filter_map(|e| {
+            let (a, b) = fresh.edge_endpoints(e)?;
+            Some((
+                fresh[a].entity.as_str(),
+                fresh[e].t.as_str(),
+                fresh[b].entity.as_str(),
+            ))
+        })
+        .collect();
+    let shown_edges: Vec<(EdgeIndex, NodeIndex, String, NodeIndex)> = g
+        .edges_iter()
+        .filter_map(|(e, edge)| {
+            let (a, b) = g.edge_endpoints(e)?;
+            Some((e, a, edge.payload().t.clone(), b))
+        })
+        .collect();
+
+    let entities: HashMap<NodeIndex, String> = index
+        .iter()
+        .map(|(entity, idx)| (*idx, entity.clone()))
+        .collect();
+    let mut existing = HashSet::new();
+    for (e, a, t, b) in shown_edges {
+        let edge = (entities[&a].as_str(), t.as_str(), entities[&b].as_str());
+        if wanted_edges.contains(&edge) {
+            existing.insert((a, t, b));
+        } else {
+            g.remove_edge(e);
+            removed.edges.insert(e);
+        }
+    }
+
+    // indices of removed elements may be reused by the ones added below
+    let selected_nodes = g
+        .selected_nodes()
+        .iter()
+        .filter(|idx| !removed.nodes.contains(*idx))
+        .copied()
+        .collect();
+    g.set_selected_nodes(selected_nodes);
+    let selected_edges = g
+        .selected_edges()
+        .iter()
+        .filter(|idx| !removed.edges.contains(*idx))
+        .copied()
+        .collect();
+    g.set_selected_edges(selected_edges);
+
+    let mut added = Vec::new();
+    for n in fresh.node_weights() {
+        if !index.contains_key(&n.entity) {
+            let idx = g.add_node_with_label(n.clone(), n.entity.clone());
+            index.insert(n.entity.clone(), idx);
+            added.push(idx);
+        }
+    }
+
+    for (a, t, b) in wanted_edges {
+        let (a, b) = (index[a], index[b]);
+        if existing.insert((a, t.to_string(), b)) {
+            let payload = EdgeData {
+                t: t.to_string(),
+                highlighted: false,
+            };
+            g.add_edge(a, b, payload);
+        }
+    }
+
+    let added_set: HashSet<NodeIndex> = added.iter().copied().collect();
+    for (i, idx) in added.into_iter().enumerate() {
+        let neighbour = g
+            .g()
+            .neighbors_undirected(idx)
+            .find(|n| !added_set.contains(n));
+        let location = match neighbour.and_then(|n| g.node(n)) {
+            Some(n) => n.location() + SPAWN_OFFSET,
+            // spread out the ones without a shown neighbour
+            None => (SPAWN_OFFSET * i as f32).to_pos2(),
+        };
+
+        if let Some(n) = g.node_mut(idx) {
+            n.set_location(location);
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Pos2;
+    use egui_graphs::Graph;
+    use rbac::ENTITY_TO_ROLE;
+
+    use super::*;
+    use crate::{
+        fixture::{self, edge, find, find_shown, node},
+        node::NodeKind,
+    };
+
+    #[test]
+    fn sync_keeps_what_is_still_there() {
+        let full = fixture::graph();
+        let mut g: ViewGraph = Graph::from(&full);
+        let group = find_shown(&g, "Group_1_Member").unwrap();
+        g.node_mut(group).unwrap().set_location(Pos2::new(10., 20.));
+        g.set_selected_nodes(vec![group]);
+
+        // alice leaves the group and bob joins it
+        let mut fresh = full.clone();
+        fresh.remove_node(find(&fresh, "User_alice"));
+        let bob = fresh.add_node(node("User_bob", NodeKind::Entity));
+        let member = find(&fresh, "Group_1_Member");
+        fresh.add_edge(bob, member, edge(ENTITY_TO_ROLE));
+
+        let alice = find_shown(&g, "User_alice").unwrap();
+        let removed = sync_graph(&mut g, &fresh);
+
+        assert_eq!(removed.nodes, HashSet::from([alice]));
+        assert_eq!(removed.edges.len(), 1);
+        assert_eq!(g.node_count(), fresh.node_count());
+        assert_eq!(g.edge_count(), fresh.edge_count());
+
+        // the group didn't move and stays selected
+        assert_eq!(find_shown(&g, "Group_1_Member"), Some(group));
+        assert_eq!(g.node(group).unwrap().location(), Pos2::new(10., 20.));
+        assert_eq!(g.selected_nodes(), &[group]);
+
+        // bob shows up next to the group
+        let bob = find_shown(&g, "User_bob").unwrap();
+        assert_eq!(
+            g.node(bob).unwrap().location(),
+            Pos2::new(10., 20.) + SPAWN_OFFSET
+        );
+        assert!(g.g().contains_edge(bob, group));
+    }
+
+    #[test]
+    fn sync_without_changes() {
+        let full = fixture::graph();
+        let mut g: ViewGraph = Graph::from(&full);
+        let before: Vec<(NodeIndex, String)> = g
+            .nodes_iter()
+            .map(|(idx, n)| (idx, n.payload().entity.clone()))
+            .collect();
+
+        let removed = sync_graph(&mut g, &full);
+
+        assert!(removed.nodes.is_empty());
+        assert!(removed.edges.is_empty());
+        assert_eq!(g.edge_count(), full.edge_count());
+        let after: Vec<(NodeIndex, String)> = g
+            .nodes_iter()
+            .map(|(idx, n)| (idx, n.payload().entity.clone()))
+            .collect();
+        assert_eq!(before, after);
+    }
+}
@@ -0,0 +1,62 @@
+// Compares the old `entity` property index lookup with reading vertices by their derived id.
+// `cargo bench -p rbac --bench vertex_lookup`
+use std::time::{Duration, Instant};
+
+use rbac::{vertex_id, RBAC};
+
+const VERTICES: usize = 10_000;
+const LOOKUPS: usize = 100_000;
+
+fn main() {
+    let rbac = RBAC::new("./rocksdb/bench_vertex_lookup");
+    rbac.clear(true).unwrap();
+
+    let entity = indradb::Identifier::new("entity").unwrap();
+    rbac.db.index_property(entity.clone()).unwrap();
+
+    let keys: Vec<String> = (0..VERTICES).map(|i| format!("User_{}", i)).collect();
+    for key in &keys {
+        let v = indradb::Vertex::with_id(vertex_id(key), indradb::Identifier::new(key).unwrap());
+        rbac.db.create_vertex(&v).unwrap();
+        rbac.db
+            .set_properties(
+                indradb::SpecificVertexQuery::single(v.id),
+                entity.clone(),
+                &indradb::Json::new(serde_json::Value::String(key.clone())),
+            )
+            .unwrap();
+    }
+
+    let by_property = measure(&keys, |key| {
+        let q = indradb::VertexWithPropertyValueQuery::new(
+            entity.clone(),
+            indradb::Json::new(serde_json::Value::String(key.to_string())),
+        );
+        rbac.db.get(q).unwrap()
+    });
+    let by_id = measure(&keys, |key| {
+        rbac.db
+            .get(indradb::SpecificVertexQuery::single(vertex_id(key)))
+            .unwrap()
+    });
+
+    report("property index", by_property);
+    report("vertex id", by_id);
+}
+
+fn measure<T>(keys: &[String], mut lookup: impl FnMut(&str) -> T) -> Duration {
+    let start = Instant::now();
+    for i in 0..LOOKUPS {
+        std::hint::black_box(lookup(&keys[i * 7919 % keys.len()]));
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{:<16} {:>10.2?} total {:>8.2?}/lookup",
+        name,
+        elapsed,
+        elapsed / LOOKUPS as u32
+    );
+}
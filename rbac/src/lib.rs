@@ -1,6 +1,30 @@
-use std::collections::VecDeque;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Mutex, PoisonError},
+};
 
 use indradb::{self, QueryExt};
+use uuid::Uuid;
+
+#[cfg(feature = "axum")]
+pub mod middleware;
+#[cfg(feature = "schema")]
+pub mod migrate;
+#[cfg(feature = "tokio")]
+pub mod nonblocking;
+#[cfg(feature = "schema")]
+pub mod schema;
+
+// default upper bound on the number of edges walked from the subject in `allowed`
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+
+// edge types
+pub const ENTITY_TO_ROLE: &str = "entity_to_role";
+pub const INHERITS: &str = "inherits";
+pub const ROLE_TO_ENTITY: &str = "role_to_entity";
+
+// set on role vertices whose holders also act as the entity (e.g. group members)
+pub const MEMBERSHIP: &str = "membership";
 
 pub trait NamespaceToString {
     fn to_string(&self) -> String;
@@ -10,7 +34,104 @@ pub trait NamespaceRole {
     fn get_roles(&self) -> Option<Box<dyn RoleHierarchy>>;
 }
 
-pub trait NamespaceToStringAndRole: NamespaceToString + NamespaceRole {}
+pub trait NamespaceToStringAndRole: NamespaceToString + NamespaceRole + Send + Sync {}
+
+// Longest escaped key part stored verbatim, longer ones are hashed. Keeps keys of three parts
+// well within indradb's 255 byte identifier limit.
+pub const MAX_KEY_PART: usize = 64;
+
+// uuid v5 namespace of vertex ids and hashed key parts
+const KEY_NAMESPACE: Uuid = Uuid::from_u128(0x3d1f_9a4e_52c7_4b0e_8f61_c2a9_7e05_d8b3);
+
+// Escapes one part of a node key (namespace name, id or role name) so that `_` only ever
+// separates parts: ASCII letters and digits are kept, every other byte becomes `-XX` in
+// uppercase hex. Parts still longer than `MAX_KEY_PART` become `-H` followed by the hex of a
+// uuid v5 of the part, which can't be decoded.
+pub fn encode_key_part(part: &str) -> String {
+    let mut encoded = String::with_capacity(part.len());
+    for b in part.bytes() {
+        if b.is_ascii_alphanumeric() {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("-{:02X}", b));
+        }
+    }
+
+    if encoded.len() > MAX_KEY_PART {
+        return format!(
+            "-H{}",
+            Uuid::new_v5(&KEY_NAMESPACE, part.as_bytes()).simple()
+        );
+    }
+
+    encoded
+}
+
+// inverse of `encode_key_part`, None for empty, hashed or non-canonical parts
+pub fn decode_key_part(part: &str) -> Option<String> {
+    let bytes = part.as_bytes();
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i].is_ascii_alphanumeric() {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        let hex = part.get(i + 1..i + 3).filter(|hex| {
+            bytes[i] == b'-'
+                && hex
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || (b'A'..=b'F').contains(&b))
+        })?;
+        let b = u8::from_str_radix(hex, 16).ok()?;
+        // letters and digits are never escaped, one spelling per key
+        if b.is_ascii_alphanumeric() {
+            return None;
+        }
+
+        decoded.push(b);
+        i += 3;
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+// Deterministic id of the vertex of a node key, the same node gets the same vertex everywhere.
+pub fn vertex_id(key: &str) -> Uuid {
+    Uuid::new_v5(&KEY_NAMESPACE, key.as_bytes())
+}
+
+// Implemented by `#[derive(Namespace)]` to turn stored keys back into namespaces.
+pub trait ParseNamespace: NamespaceToStringAndRole + Sized + 'static {
+    // variant names, e.g. `["User", "Post", "Group"]`
+    const NAMES: &'static [&'static str];
+
+    fn from_name(name: &str, id: Option<String>) -> Option<Self>;
+
+    // `Post_1` -> `("Post", Some("1"))`, `Post` -> `("Post", None)`
+    fn split_key(key: &str) -> Option<(&'static str, Option<String>)> {
+        let (name, id) = match key.split_once('_') {
+            Some((name, id)) => (name, Some(decode_key_part(id)?)),
+            None => (key, None),
+        };
+        let name = decode_key_part(name)?;
+
+        Self::NAMES
+            .iter()
+            .find(|known| **known == name)
+            .map(|known| (*known, id))
+    }
+}
+
+// a key or name that isn't a node of the expected namespaces or roles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNodeError(pub String);
 
 // Node can be Entity or Role
 pub struct Node {
@@ -23,16 +144,68 @@ impl Node {
         Node { namespace, id }
     }
 
+    // `{namespace}_{id}` for entities, `{namespace}_{object id}_{role}` for roles, every part
+    // escaped by `encode_key_part`
     pub fn to_string(&self) -> String {
-        format!("{}_{}", self.namespace.to_string(), self.id)
+        format!(
+            "{}_{}",
+            self.namespace.to_string(),
+            encode_key_part(&self.id)
+        )
+    }
+
+    // for entities the entity's id, for roles the role name
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    // for roles this includes the object's id, e.g. `Post_1` of `Post_1_Viewer`
+    pub fn namespace(&self) -> &dyn NamespaceToStringAndRole {
+        self.namespace.as_ref()
     }
 
+    // Inverse of `to_string`, e.g. `Node::parse::<Namespaces>("Post_1_Viewer")`. Keys with
+    // hashed parts can't be parsed back.
+    pub fn parse<N: ParseNamespace>(key: &str) -> Result<Node, ParseNodeError> {
+        let error = || ParseNodeError(key.to_string());
+
+        let parts = key
+            .split('_')
+            .map(decode_key_part)
+            .collect::<Option<Vec<String>>>()
+            .ok_or_else(error)?;
+
+        match &parts[..] {
+            [name, id] => {
+                let namespace = N::from_name(name, None).ok_or_else(error)?;
+                Ok(Node::new(Box::new(namespace), id.clone()))
+            }
+            [name, object_id, role] => {
+                let namespace = N::from_name(name, Some(object_id.clone())).ok_or_else(error)?;
+
+                // only roles of the namespace
+                let mut known = false;
+                if let Some(roles) = namespace.get_roles() {
+                    roles.iter_all(&mut |r| known |= r.to_node(None).id == *role);
+                }
+                if !known {
+                    return Err(error());
+                }
+
+                Ok(Node::new(Box::new(namespace), role.clone()))
+            }
+            _ => Err(error()),
+        }
+    }
+
+    // escaped parts are at most `MAX_KEY_PART` long, so keys always make valid identifiers
     pub fn to_identifier(&self) -> indradb::Identifier {
         indradb::Identifier::new(self.to_string()).unwrap()
     }
 
     pub fn to_vertex(&self) -> indradb::Vertex {
-        indradb::Vertex::new(self.to_identifier())
+        let key = self.to_string();
+        indradb::Vertex::with_id(vertex_id(&key), indradb::Identifier::new(key).unwrap())
     }
 }
 
@@ -43,9 +216,28 @@ pub trait ToNode {
 pub trait RoleHierarchy: ToNode {
     fn iter_hierarchy(&self, f: &mut dyn FnMut(Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>));
     fn iter_all(&self, f: &mut dyn FnMut(Box<dyn RoleHierarchy>));
+    fn is_membership(&self) -> bool {
+        false
+    }
+    // `f(role, implied_by, via)`: holders of `implied_by` on an entity that holds `via` on an
+    // object also hold `role` on it, e.g. admins of the group owning a post are its writers
+    fn iter_implied(
+        &self,
+        _f: &mut dyn FnMut(Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>),
+    ) {
+    }
     //fn as_any(&self) -> &dyn Any;
 }
 
+// Implemented by `#[derive(ToNode)]` for entities of the namespace variant whose id is `NS`
+// (see `rbac_macro`), so that `#[derive(Role)]`'s `check` only takes objects of its own
+// namespace.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not an entity of the role's namespace",
+    label = "roles can only be checked against objects of their own namespace"
+)]
+pub trait InNamespace<const NS: u64>: ToNode {}
+
 //
 
 pub struct EntityRelationship {
@@ -73,6 +265,30 @@ impl EntityRelationship {
     }
 }
 
+// how `RBAC::write_relationship` treats a relationship that's already written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteMode {
+    // upsert, writing it again changes nothing
+    Touch,
+    // fails with `RBACError::RelationshipExists`
+    Create,
+}
+
+// Checked against the written relationships, not computed permissions, before a write
+pub enum Precondition {
+    MustExist(EntityRelationship),
+    MustNotExist(EntityRelationship),
+}
+
+// one change of `RBAC::write_batch`
+pub enum Update {
+    // written with `WriteMode::Touch`
+    Touch(EntityRelationship),
+    // written with `WriteMode::Create`, fails the batch when already written
+    Create(EntityRelationship),
+    Delete(EntityRelationship),
+}
+
 pub struct RoleRelationship {
     parent: Node,
     child: Node,
@@ -95,6 +311,9 @@ impl RoleRelationship {
 
 pub struct RBAC {
     pub db: indradb::Database<indradb::RocksdbDatastore>,
+    max_depth: usize,
+    // serializes writes so preconditions and existence checks hold until the write is done
+    writes: Mutex<()>,
 }
 
 #[derive(Debug)]
@@ -102,6 +321,15 @@ pub enum RBACError {
     IndradbError(indradb::Error),
     VertexNotFound,
     VertexDuplication,
+    // traversal hit the configured max depth before a grant was found
+    DepthLimitExceeded(usize),
+    // `WriteMode::Create` of a relationship that's already written
+    RelationshipExists,
+    // index of the first precondition that didn't hold, nothing was written
+    PreconditionFailed(usize),
+    // a `nonblocking::AsyncRBAC` call was dropped before it finished, e.g. because the
+    // threads of its pool are gone
+    Cancelled,
 }
 
 impl From<indradb::Error> for RBACError {
@@ -110,12 +338,37 @@ impl From<indradb::Error> for RBACError {
     }
 }
 
+// outcome of `RBAC::diagnose`, as entity keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    // from the subject to the object, None when denied
+    pub path: Option<Vec<String>>,
+    // a cycle the denied check walked through, the first key is repeated at the end
+    pub cycle: Option<Vec<String>>,
+}
+
 impl RBAC {
     pub fn new(db_path: &str) -> Self {
-        let db = indradb::RocksdbDatastore::new_db(db_path).unwrap();
-        db.index_property(indradb::Identifier::new("entity").unwrap())
-            .unwrap();
-        RBAC { db }
+        Self::open(db_path).unwrap()
+    }
+
+    // like `new`, but failing to open the store is an error instead of a panic
+    pub fn open(db_path: &str) -> Result<Self, RBACError> {
+        let db = indradb::RocksdbDatastore::new_db(db_path)?;
+        Ok(RBAC {
+            db,
+            max_depth: DEFAULT_MAX_DEPTH,
+            writes: Mutex::new(()),
+        })
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
     }
 
     pub fn get_all_vertices(&self) -> Result<Vec<indradb::Vertex>, RBACError> {
@@ -136,6 +389,22 @@ impl RBAC {
         Ok(edges)
     }
 
+    // properties of the vertex keyed `key` by name, e.g. `membership`
+    pub fn get_vertex_properties(
+        &self,
+        key: &str,
+    ) -> Result<Vec<(String, serde_json::Value)>, RBACError> {
+        let q = indradb::SpecificVertexQuery::single(vertex_id(key)).properties()?;
+        let output = self.db.get(q)?;
+
+        Ok(indradb::util::extract_vertex_properties(output)
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|vp| vp.props)
+            .map(|p| (p.name.to_string(), (*p.value).clone()))
+            .collect())
+    }
+
     pub fn clear(&self, really: bool) -> Result<(), RBACError> {
         if really {
             self.db.delete(indradb::AllVertexQuery)?;
@@ -144,61 +413,26 @@ impl RBAC {
         Ok(())
     }
 
+    // Vertex ids follow from node keys, so creating is a single idempotent write and concurrent
+    // creates of the same node end up on the same vertex.
     fn get_or_create_vertex(&self, node: &Node) -> Result<(indradb::Vertex, bool), RBACError> {
-        let entity_identifier = indradb::Identifier::new("entity").unwrap();
-        let entity_value = indradb::Json::new(serde_json::Value::String(node.to_string()));
-
-        let q = indradb::VertexWithPropertyValueQuery::new(
-            entity_identifier.clone(),
-            entity_value.clone(),
-        );
-        let output = self.db.get(q)?;
-
-        let vertices = match indradb::util::extract_vertices(output) {
-            Some(vs) => vs,
-            None => return Err(RBACError::VertexNotFound),
-        };
-
-        if vertices.is_empty() {
-            let v = node.to_vertex();
-            self.db.create_vertex(&v)?;
-            self.db.set_properties(
-                indradb::SpecificVertexQuery::single(v.id.clone()),
-                entity_identifier,
-                &entity_value,
-            )?;
-
-            return Ok((v, false));
-        }
-
-        if vertices.len() > 1 {
-            return Err(RBACError::VertexDuplication);
-        }
-
-        Ok((vertices[0].clone(), true))
+        let v = node.to_vertex();
+        let created = self.db.create_vertex(&v)?;
+        Ok((v, !created))
     }
 
     fn get_vertex(&self, node: &Node) -> Result<indradb::Vertex, RBACError> {
-        let q = indradb::VertexWithPropertyValueQuery::new(
-            indradb::Identifier::new("entity").unwrap(),
-            indradb::Json::new(serde_json::Value::String(node.to_string())),
-        );
-        let output = self.db.get(q)?;
-
-        let vertices = match indradb::util::extract_vertices(output) {
-            Some(vs) => vs,
-            None => return Err(RBACError::VertexNotFound),
-        };
-
-        if vertices.is_empty() {
-            return Err(RBACError::VertexNotFound);
-        }
+        self.get_vertex_by_key(&node.to_string())
+    }
 
-        if vertices.len() > 1 {
-            return Err(RBACError::VertexDuplication);
-        }
+    fn get_vertex_by_key(&self, key: &str) -> Result<indradb::Vertex, RBACError> {
+        let output = self
+            .db
+            .get(indradb::SpecificVertexQuery::single(vertex_id(key)))?;
 
-        Ok(vertices[0].clone())
+        indradb::util::extract_vertices(output)
+            .and_then(|vs| vs.into_iter().next())
+            .ok_or(RBACError::VertexNotFound)
     }
 
     pub fn add_role_relationship(
@@ -210,7 +444,7 @@ impl RBAC {
 
         let e = indradb::Edge::new(
             parent_v.id,
-            indradb::Identifier::new("inherits").unwrap(),
+            indradb::Identifier::new(INHERITS).unwrap(),
             child_v.id,
         );
 
@@ -219,42 +453,90 @@ impl RBAC {
         Ok(true)
     }
 
+    // true when the relationship is new, false when it was already written
     pub fn add_relationship(&self, relationship: &EntityRelationship) -> Result<bool, RBACError> {
-        let (subject_v, _) = self.get_or_create_vertex(&relationship.subject)?;
-        let (role_v, _) = self.get_or_create_vertex(&relationship.role)?;
-        let (object_v, was_object_exist) = self.get_or_create_vertex(&relationship.object)?;
-
-        // newly created object vertex
-        if !was_object_exist {
-            // handle role's hierarchy
-            let roles = relationship.object.namespace.get_roles();
-            if let Some(roles) = roles {
-                roles.iter_hierarchy(&mut |parent, child| {
-                    let parent_node = parent.to_node(Some(relationship.object.id.clone()));
-                    let child_node = child.to_node(Some(relationship.object.id.clone()));
-                    self.add_role_relationship(&RoleRelationship::new_from_node(
-                        parent_node,
-                        child_node,
-                    ))
-                    .unwrap();
-                });
-
-                roles.iter_all(&mut |role| {
-                    let role_node = role.to_node(Some(relationship.object.id.clone()));
-                    let (role_v, _) = self.get_or_create_vertex(&role_node).unwrap();
-                    let role_e = indradb::Edge::new(
-                        role_v.id,
-                        indradb::Identifier::new("role_to_entity").unwrap(),
-                        object_v.id,
-                    );
-                    self.db.create_edge(&role_e).unwrap();
-                });
+        self.write_relationship(relationship, WriteMode::Touch, &[])
+    }
+
+    // Writes `relationship` if every precondition holds. Returns whether it's new, `mode`
+    // decides whether an existing one is an error.
+    pub fn write_relationship(
+        &self,
+        relationship: &EntityRelationship,
+        mode: WriteMode,
+        preconditions: &[Precondition],
+    ) -> Result<bool, RBACError> {
+        let _writes = self.writes.lock().unwrap_or_else(PoisonError::into_inner);
+        self.write_locked(relationship, mode, preconditions)
+    }
+
+    // Applies `updates` in order as one write, returning for each whether it changed the store.
+    // When one fails the ones before it are undone, so the batch is written whole or not at
+    // all. Should undoing fail too, that error is returned and the store may hold part of it.
+    pub fn write_batch(&self, updates: &[Update]) -> Result<Vec<bool>, RBACError> {
+        let _writes = self.writes.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut changed = Vec::with_capacity(updates.len());
+        for update in updates {
+            let result = match update {
+                Update::Touch(r) => self.write_locked(r, WriteMode::Touch, &[]),
+                Update::Create(r) => self.write_locked(r, WriteMode::Create, &[]),
+                Update::Delete(r) => self.remove_locked(r),
+            };
+
+            match result {
+                Ok(c) => changed.push(c),
+                Err(e) => {
+                    for (update, _) in updates.iter().zip(&changed).rev().filter(|(_, c)| **c) {
+                        match update {
+                            Update::Touch(r) | Update::Create(r) => {
+                                self.remove_locked(r)?;
+                            }
+                            Update::Delete(r) => {
+                                self.db.create_edge(&relationship_edge(r))?;
+                            }
+                        }
+                    }
+                    return Err(e);
+                }
             }
         }
 
+        Ok(changed)
+    }
+
+    // `write_relationship` with `writes` held
+    fn write_locked(
+        &self,
+        relationship: &EntityRelationship,
+        mode: WriteMode,
+        preconditions: &[Precondition],
+    ) -> Result<bool, RBACError> {
+        for (i, precondition) in preconditions.iter().enumerate() {
+            let holds = match precondition {
+                Precondition::MustExist(r) => self.has_relationship(r)?,
+                Precondition::MustNotExist(r) => !self.has_relationship(r)?,
+            };
+            if !holds {
+                return Err(RBACError::PreconditionFailed(i));
+            }
+        }
+
+        if self.has_relationship(relationship)? {
+            return match mode {
+                WriteMode::Touch => Ok(false),
+                WriteMode::Create => Err(RBACError::RelationshipExists),
+            };
+        }
+
+        let (subject_v, _) = self.get_or_create_vertex(&relationship.subject)?;
+        let (role_v, _) = self.get_or_create_vertex(&relationship.role)?;
+        let (object_v, _) = self.get_or_create_vertex(&relationship.object)?;
+        self.materialize_roles(&relationship.object, object_v.id)?;
+
         let subject_e = indradb::Edge::new(
             subject_v.id,
-            indradb::Identifier::new("entity_to_role").unwrap(),
+            indradb::Identifier::new(ENTITY_TO_ROLE).unwrap(),
             role_v.id,
         );
 
@@ -263,6 +545,68 @@ impl RBAC {
         Ok(true)
     }
 
+    // Writes the role vertices of `object`, their hierarchy, membership flags and
+    // `role_to_entity` edges unless they're all there, so an entity first written as a subject
+    // gets them once it's used as an object. The `role_to_entity` edges go last: when every role
+    // has one, the rest was written too.
+    fn materialize_roles(&self, object: &Node, object_v: Uuid) -> Result<(), RBACError> {
+        let roles = match object.namespace.get_roles() {
+            Some(roles) => roles,
+            None => return Ok(()),
+        };
+
+        let mut all = Vec::new();
+        roles.iter_all(&mut |role| {
+            all.push((role.to_node(Some(object.id.clone())), role.is_membership()));
+        });
+        let written: HashSet<Uuid> = self
+            .edges(&[object_v], Direction::Inbound, ROLE_TO_ENTITY)?
+            .into_iter()
+            .map(|e| e.outbound_id)
+            .collect();
+        if all
+            .iter()
+            .all(|(role, _)| written.contains(&vertex_id(&role.to_string())))
+        {
+            return Ok(());
+        }
+
+        let mut hierarchy = Vec::new();
+        roles.iter_hierarchy(&mut |parent, child| {
+            hierarchy.push(RoleRelationship::new_from_node(
+                parent.to_node(Some(object.id.clone())),
+                child.to_node(Some(object.id.clone())),
+            ));
+        });
+        for relationship in &hierarchy {
+            self.add_role_relationship(relationship)?;
+        }
+
+        let mut role_vertices = Vec::new();
+        for (role, membership) in &all {
+            let (role_v, _) = self.get_or_create_vertex(role)?;
+            if *membership {
+                self.db.set_properties(
+                    indradb::SpecificVertexQuery::single(role_v.id),
+                    indradb::Identifier::new(MEMBERSHIP).unwrap(),
+                    &indradb::Json::new(serde_json::Value::Bool(true)),
+                )?;
+            }
+            role_vertices.push(role_v);
+        }
+
+        for role_v in role_vertices {
+            let role_e = indradb::Edge::new(
+                role_v.id,
+                indradb::Identifier::new(ROLE_TO_ENTITY).unwrap(),
+                object_v,
+            );
+            self.db.create_edge(&role_e)?;
+        }
+
+        Ok(())
+    }
+
     // pub fn vertex_count(&self) -> Result<usize, RBACError> {
     //     let output = self.db.get(indradb::AllVertexQuery)?;
     //     // let vertices = match indradb::util::extract_vertices(output) {
@@ -272,61 +616,638 @@ impl RBAC {
     //     Ok(output.len())
     // }
 
+    // Walks the graph level by level, following only the edges that can carry a grant:
+    //   entity -entity_to_role-> role        the entity holds the role
+    //   role   -inherits->       role        holding the parent implies the child
+    //   role   -role_to_entity-> entity      only from membership roles, the holder acts as the entity
+    // so the traversal never steps into objects through roles that don't confer membership.
+    // Roles implied across namespaces (`RoleHierarchy::iter_implied`) are checked afterwards.
     pub fn allowed(&self, target: &EntityRelationship) -> Result<bool, RBACError> {
+        Ok(self.check(target, &mut CheckState::default())?.is_some())
+    }
+
+    // entity keys along the path that grants `target`, from the subject to the object.
+    // None when the check is denied.
+    pub fn explain(&self, target: &EntityRelationship) -> Result<Option<Vec<String>>, RBACError> {
+        match self.check(target, &mut CheckState::default())? {
+            Some(path) => Ok(Some(self.entity_keys(&path)?)),
+            None => Ok(None),
+        }
+    }
+
+    // like `explain`, and a denial also reports a cycle the traversal ran into, if any.
+    // cycles don't change the outcome, they're reported for cleaning up the relationships.
+    pub fn diagnose(&self, target: &EntityRelationship) -> Result<Diagnosis, RBACError> {
+        let mut state = CheckState::default();
+        let path = self.check(target, &mut state)?;
+
+        Ok(Diagnosis {
+            path: match path {
+                Some(path) => Some(self.entity_keys(&path)?),
+                None => None,
+            },
+            cycle: match state.cycle {
+                Some(cycle) => Some(self.entity_keys(&cycle)?),
+                None => None,
+            },
+        })
+    }
+
+    fn check(
+        &self,
+        target: &EntityRelationship,
+        state: &mut CheckState,
+    ) -> Result<Option<Vec<Uuid>>, RBACError> {
         let subject_v = self.get_vertex(&target.subject)?;
-        let role_v = self.get_vertex(&target.role)?;
-        let object_v = self.get_vertex(&target.object)?;
+        self.check_from(
+            subject_v.id,
+            &target.role.to_string(),
+            &target.role.id,
+            &target.object.to_string(),
+            target.object.namespace.get_roles(),
+            state,
+        )
+    }
 
-        let mut queue = VecDeque::new();
-        queue.push_back(subject_v.id.clone());
+    // `roles` are the object namespace's, for implications. `state.in_progress` holds the role
+    // vertices being checked further up, so implications looping back onto them are skipped.
+    fn check_from(
+        &self,
+        subject: Uuid,
+        role_key: &str,
+        role: &str,
+        object_key: &str,
+        roles: Option<Box<dyn RoleHierarchy>>,
+        state: &mut CheckState,
+    ) -> Result<Option<Vec<Uuid>>, RBACError> {
+        if !state.in_progress.insert(role_key.to_string()) {
+            return Ok(None);
+        }
 
-        let mut visited = Vec::new();
-        visited.push(subject_v.id.clone());
+        let role_v = self.get_vertex_by_key(role_key)?;
+        let object_v = self.get_vertex_by_key(object_key)?;
 
-        while !queue.is_empty() {
-            let v = queue.pop_front().unwrap();
+        // acting as the target object itself never grants a role on it
+        let walk = self.walk(
+            subject,
+            Direction::Outbound,
+            Some(object_v.id),
+            &mut |_, to| to == role_v.id,
+        )?;
 
-            let q = indradb::SpecificVertexQuery::single(v).outbound().unwrap();
-            let output = self.db.get(q)?;
+        if let Some(mut path) = walk.path {
+            path.push(object_v.id);
+            return Ok(Some(path));
+        }
 
-            println!("cur: {:?}", v);
-            for o in output.clone() {
-                println!("outbound: {:?}", o);
+        if let Some(roles) = roles {
+            let implied =
+                self.check_implied(subject, role, object_key, object_v.id, roles, state)?;
+            if let Some(path) = implied {
+                return Ok(Some(path));
             }
+        }
 
-            let vertices = indradb::util::extract_vertices(output.clone());
+        // a grant may be hidden behind the cut-off, so don't report a plain denial
+        if walk.depth_exceeded {
+            return Err(RBACError::DepthLimitExceeded(self.max_depth));
+        }
 
-            if let Some(vertices) = vertices {
-                for vertex in vertices {
-                    if vertex.id == object_v.id {
-                        //return Ok(true);
-                    } else {
-                        if !visited.contains(&vertex.id) {
-                            visited.push(vertex.id.clone());
-                            queue.push_back(vertex.id.clone());
-                        }
+        if state.cycle.is_none() {
+            state.cycle = find_cycle(&walk.followed, subject);
+        }
+
+        Ok(None)
+    }
+
+    // Grants from `iter_implied` of the object's namespace. They aren't stored as edges but
+    // evaluated here: for every entity holding `via` on the object, directly, through a parent
+    // role or as a member of a holder, check whether the subject holds `implied_by` on it.
+    fn check_implied(
+        &self,
+        subject: Uuid,
+        role: &str,
+        object_key: &str,
+        object_v: Uuid,
+        roles: Box<dyn RoleHierarchy>,
+        state: &mut CheckState,
+    ) -> Result<Option<Vec<Uuid>>, RBACError> {
+        for (implied_by, via) in implied_rules(roles.as_ref(), role) {
+            let via_key = format!("{}_{}", object_key, encode_key_part(&via.id));
+            let via_v = match self.get_vertex_by_key(&via_key) {
+                Ok(v) => v,
+                Err(RBACError::VertexNotFound) => continue,
+                Err(e) => return Err(e),
+            };
+
+            // only holders of the `implied_by` role's namespace
+            let prefix = format!("{}_", implied_by.namespace.to_string());
+            for key in self.entity_keys(&self.holders(via_v.id)?)? {
+                if !key.starts_with(&prefix) {
+                    continue;
+                }
+
+                let result = self.check_from(
+                    subject,
+                    &format!("{}_{}", key, encode_key_part(&implied_by.id)),
+                    &implied_by.id,
+                    &key,
+                    implied_by.namespace.get_roles(),
+                    state,
+                );
+                match result {
+                    Ok(Some(mut path)) => {
+                        path.push(via_v.id);
+                        path.push(object_v);
+                        return Ok(Some(path));
                     }
+                    // the holder's roles were never materialized
+                    Ok(None) | Err(RBACError::VertexNotFound) => {}
+                    Err(e) => return Err(e),
                 }
             }
+        }
 
-            let edges = indradb::util::extract_edges(output);
+        Ok(None)
+    }
 
-            if let Some(edges) = edges {
-                for edge in edges {
-                    if edge.inbound_id == object_v.id {
-                        if edge.outbound_id == role_v.id {
-                            return Ok(true);
-                        }
-                    } else {
-                        if !visited.contains(&edge.inbound_id) {
-                            visited.push(edge.inbound_id.clone());
-                            queue.push_back(edge.inbound_id.clone());
-                        }
+    pub fn remove_relationship(
+        &self,
+        relationship: &EntityRelationship,
+    ) -> Result<bool, RBACError> {
+        let _writes = self.writes.lock().unwrap_or_else(PoisonError::into_inner);
+        self.remove_locked(relationship)
+    }
+
+    fn remove_locked(&self, relationship: &EntityRelationship) -> Result<bool, RBACError> {
+        if !self.has_relationship(relationship)? {
+            return Ok(false);
+        }
+
+        let e = relationship_edge(relationship);
+        self.db.delete(indradb::SpecificEdgeQuery::single(e))?;
+
+        Ok(true)
+    }
+
+    // whether `relationship` itself is written, grants through groups or roles don't count
+    pub fn has_relationship(&self, relationship: &EntityRelationship) -> Result<bool, RBACError> {
+        let e = relationship_edge(relationship);
+        let output = self.db.get(indradb::SpecificEdgeQuery::single(e))?;
+
+        Ok(!indradb::util::extract_edges(output)
+            .unwrap_or_default()
+            .is_empty())
+    }
+
+    // entity keys of the objects on which `subject` holds `role`, directly, through groups or
+    // implied by a role on another entity, the same grants `allowed` follows
+    pub fn lookup_objects(
+        &self,
+        subject: &dyn ToNode,
+        role: &dyn ToNode,
+    ) -> Result<Vec<String>, RBACError> {
+        let subject_v = self.get_vertex(&subject.to_node(None))?;
+        let objects = self.objects_with(subject_v.id, &role.to_node(None), &mut HashSet::new())?;
+
+        let mut keys = self.entity_keys(&objects)?;
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    // like `held_objects`, plus the objects `iter_implied` grants `role` on. `visited` holds
+    // the subjects and roles looked up further up, implications looping back are skipped.
+    fn objects_with(
+        &self,
+        subject: Uuid,
+        role: &Node,
+        visited: &mut HashSet<(Uuid, String)>,
+    ) -> Result<Vec<Uuid>, RBACError> {
+        if !visited.insert((subject, role.to_string())) {
+            return Ok(Vec::new());
+        }
+
+        let mut objects = self.held_objects(subject, role)?;
+
+        if let Some(roles) = role.namespace.get_roles() {
+            for (implied_by, via) in implied_rules(roles.as_ref(), &role.id) {
+                for entity in self.objects_with(subject, &implied_by, visited)? {
+                    objects.extend(self.held_objects(entity, &via)?);
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+
+    // objects on which `subject` holds `role` through stored relationships
+    fn held_objects(&self, subject: Uuid, role: &Node) -> Result<Vec<Uuid>, RBACError> {
+        let mut held = Vec::new();
+        let walk = self.walk(subject, Direction::Outbound, None, &mut |edge, to| {
+            if edge.t.as_str() != ROLE_TO_ENTITY {
+                held.push(to);
+            }
+            false
+        })?;
+
+        if walk.depth_exceeded {
+            return Err(RBACError::DepthLimitExceeded(self.max_depth));
+        }
+
+        // role vertices are keyed `{Namespace}_{object id}_{Role}`
+        let prefix = format!("{}_", role.namespace.to_string());
+        let suffix = format!("_{}", encode_key_part(&role.id));
+        let keys = self.entity_keys(&held)?;
+        let matching: Vec<Uuid> = held
+            .into_iter()
+            .zip(keys)
+            .filter(|(_, key)| key.starts_with(&prefix) && key.ends_with(&suffix))
+            .map(|(id, _)| id)
+            .collect();
+
+        Ok(self
+            .edges(&matching, Direction::Outbound, ROLE_TO_ENTITY)?
+            .into_iter()
+            .map(|e| e.inbound_id)
+            .collect())
+    }
+
+    // entity keys of every subject holding `role` on `object`, including groups, their members
+    // and the holders of roles implying it
+    pub fn lookup_subjects(
+        &self,
+        role: &dyn ToNode,
+        object: &dyn ToNode,
+    ) -> Result<Vec<String>, RBACError> {
+        let object = object.to_node(None);
+        let role = role.to_node(Some(object.id.clone()));
+
+        let subjects = self.subjects_with(
+            &role.to_string(),
+            &role.id,
+            &object.to_string(),
+            object.namespace.get_roles(),
+            &mut HashSet::new(),
+        )?;
+
+        let mut keys = self.entity_keys(&subjects)?;
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    // like `holders`, plus the subjects `iter_implied` grants the role to. `roles` and
+    // `visited` are as for `check_from`.
+    fn subjects_with(
+        &self,
+        role_key: &str,
+        role: &str,
+        object_key: &str,
+        roles: Option<Box<dyn RoleHierarchy>>,
+        visited: &mut HashSet<String>,
+    ) -> Result<Vec<Uuid>, RBACError> {
+        if !visited.insert(role_key.to_string()) {
+            return Ok(Vec::new());
+        }
+
+        let role_v = self.get_vertex_by_key(role_key)?;
+        let mut subjects = self.holders(role_v.id)?;
+
+        let roles = match roles {
+            Some(roles) => roles,
+            None => return Ok(subjects),
+        };
+
+        for (implied_by, via) in implied_rules(roles.as_ref(), role) {
+            let via_key = format!("{}_{}", object_key, encode_key_part(&via.id));
+            let via_v = match self.get_vertex_by_key(&via_key) {
+                Ok(v) => v,
+                Err(RBACError::VertexNotFound) => continue,
+                Err(e) => return Err(e),
+            };
+
+            let prefix = format!("{}_", implied_by.namespace.to_string());
+            for key in self.entity_keys(&self.holders(via_v.id)?)? {
+                if !key.starts_with(&prefix) {
+                    continue;
+                }
+
+                let result = self.subjects_with(
+                    &format!("{}_{}", key, encode_key_part(&implied_by.id)),
+                    &implied_by.id,
+                    &key,
+                    implied_by.namespace.get_roles(),
+                    visited,
+                );
+                match result {
+                    Ok(found) => subjects.extend(found),
+                    // the holder's roles were never materialized
+                    Err(RBACError::VertexNotFound) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        Ok(subjects)
+    }
+
+    // entities holding the role `role_v` through stored relationships: directly, through a
+    // parent role or as members of a holder
+    fn holders(&self, role_v: Uuid) -> Result<Vec<Uuid>, RBACError> {
+        let mut holders = Vec::new();
+        let walk = self.walk(role_v, Direction::Inbound, None, &mut |edge, to| {
+            if edge.t.as_str() == ENTITY_TO_ROLE {
+                holders.push(to);
+            }
+            false
+        })?;
+
+        if walk.depth_exceeded {
+            return Err(RBACError::DepthLimitExceeded(self.max_depth));
+        }
+
+        Ok(holders)
+    }
+
+    // Breadth-first walk shared by checks and lookups. Outbound goes from a subject towards
+    // the roles it holds, inbound goes from a role back to its holders over the same edges.
+    // `visit` sees every edge reaching a new vertex and stops the walk by returning true.
+    fn walk(
+        &self,
+        start: Uuid,
+        direction: Direction,
+        skip: Option<Uuid>,
+        visit: &mut dyn FnMut(&indradb::Edge, Uuid) -> bool,
+    ) -> Result<Walk, RBACError> {
+        let (mut entities, mut roles) = match direction {
+            Direction::Outbound => (vec![start], Vec::new()),
+            Direction::Inbound => (Vec::new(), vec![start]),
+        };
+
+        // vertex -> its parent on the bfs tree, used to recover the path
+        let mut parents: HashMap<Uuid, Option<Uuid>> = HashMap::new();
+        parents.insert(start, None);
+
+        let mut walk = Walk {
+            path: None,
+            depth_exceeded: false,
+            followed: HashMap::new(),
+        };
+        let mut depth = 0;
+
+        while !entities.is_empty() || !roles.is_empty() {
+            let edges = match direction {
+                Direction::Outbound => {
+                    let members = self.membership_roles(&roles)?;
+
+                    let mut edges = self.edges(&entities, direction, ENTITY_TO_ROLE)?;
+                    edges.extend(self.edges(&roles, direction, INHERITS)?);
+                    edges.extend(self.edges(&members, direction, ROLE_TO_ENTITY)?);
+                    edges
+                }
+                Direction::Inbound => {
+                    let mut edges = self.edges(&roles, direction, INHERITS)?;
+                    edges.extend(self.edges(&roles, direction, ENTITY_TO_ROLE)?);
+
+                    let granted = self.edges(&entities, direction, ROLE_TO_ENTITY)?;
+                    let sources: Vec<Uuid> = granted.iter().map(|e| e.outbound_id).collect();
+                    let members: HashSet<Uuid> =
+                        self.membership_roles(&sources)?.into_iter().collect();
+                    edges.extend(
+                        granted
+                            .into_iter()
+                            .filter(|e| members.contains(&e.outbound_id)),
+                    );
+                    edges
+                }
+            };
+
+            let mut next_entities = Vec::new();
+            let mut next_roles = Vec::new();
+
+            for edge in edges {
+                let (from, to) = match direction {
+                    Direction::Outbound => (edge.outbound_id, edge.inbound_id),
+                    Direction::Inbound => (edge.inbound_id, edge.outbound_id),
+                };
+
+                if Some(to) == skip {
+                    continue;
+                }
+
+                walk.followed.entry(from).or_default().push(to);
+
+                if parents.contains_key(&to) {
+                    continue;
+                }
+
+                if depth >= self.max_depth {
+                    walk.depth_exceeded = true;
+                    continue;
+                }
+
+                if visit(&edge, to) {
+                    let mut path = vec![to, from];
+                    let mut cur = from;
+                    while let Some(Some(parent)) = parents.get(&cur) {
+                        path.push(*parent);
+                        cur = *parent;
                     }
+                    path.reverse();
+
+                    walk.path = Some(path);
+                    return Ok(walk);
                 }
+
+                parents.insert(to, Some(from));
+
+                let to_entity = match direction {
+                    Direction::Outbound => edge.t.as_str() == ROLE_TO_ENTITY,
+                    Direction::Inbound => edge.t.as_str() == ENTITY_TO_ROLE,
+                };
+                if to_entity {
+                    next_entities.push(to);
+                } else {
+                    next_roles.push(to);
+                }
+            }
+
+            entities = next_entities;
+            roles = next_roles;
+            depth += 1;
+        }
+
+        Ok(walk)
+    }
+
+    fn edges(
+        &self,
+        ids: &[Uuid],
+        direction: Direction,
+        t: &str,
+    ) -> Result<Vec<indradb::Edge>, RBACError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let vertices = indradb::SpecificVertexQuery::new(ids.to_vec());
+        let q = match direction {
+            Direction::Outbound => vertices.outbound()?,
+            Direction::Inbound => vertices.inbound()?,
+        };
+        let output = self.db.get(q.t(indradb::Identifier::new(t).unwrap()))?;
+
+        Ok(indradb::util::extract_edges(output).unwrap_or_default())
+    }
+
+    fn membership_roles(&self, ids: &[Uuid]) -> Result<Vec<Uuid>, RBACError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let membership = indradb::Identifier::new(MEMBERSHIP).unwrap();
+        let q = indradb::SpecificVertexQuery::new(ids.to_vec())
+            .properties()?
+            .name(membership.clone());
+        let output = self.db.get(q)?;
+
+        let marked = indradb::Json::new(serde_json::Value::Bool(true));
+        let roles = indradb::util::extract_vertex_properties(output)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|vp| {
+                vp.props
+                    .iter()
+                    .any(|p| p.name == membership && p.value == marked)
+            })
+            .map(|vp| vp.vertex.id)
+            .collect();
+
+        Ok(roles)
+    }
+
+    fn entity_keys(&self, ids: &[Uuid]) -> Result<Vec<String>, RBACError> {
+        let unique: HashSet<Uuid> = ids.iter().copied().collect();
+        let output = self.db.get(indradb::SpecificVertexQuery::new(
+            unique.into_iter().collect(),
+        ))?;
+
+        let keys: HashMap<Uuid, String> = indradb::util::extract_vertices(output)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|v| (v.id, v.t.to_string()))
+            .collect();
+
+        ids.iter()
+            .map(|id| keys.get(id).cloned().ok_or(RBACError::VertexNotFound))
+            .collect()
+    }
+}
+
+// subject -entity_to_role-> role, vertex ids follow from the node keys
+fn relationship_edge(relationship: &EntityRelationship) -> indradb::Edge {
+    indradb::Edge::new(
+        vertex_id(&relationship.subject.to_string()),
+        indradb::Identifier::new(ENTITY_TO_ROLE).unwrap(),
+        vertex_id(&relationship.role.to_string()),
+    )
+}
+
+// `(implied_by, via)` of the implications granting `role` or one of the roles implying it
+// through the hierarchy
+fn implied_rules(roles: &dyn RoleHierarchy, role: &str) -> Vec<(Node, Node)> {
+    let mut hierarchy = Vec::new();
+    roles.iter_hierarchy(&mut |parent, child| {
+        hierarchy.push((parent.to_node(None).id, child.to_node(None).id));
+    });
+
+    // `role` and its ancestors
+    let mut implying = HashSet::from([role.to_string()]);
+    loop {
+        let before = implying.len();
+        for (parent, child) in &hierarchy {
+            if implying.contains(child) {
+                implying.insert(parent.clone());
             }
         }
+        if implying.len() == before {
+            break;
+        }
+    }
 
-        Ok(false)
+    let mut rules = Vec::new();
+    roles.iter_implied(&mut |implied, implied_by, via| {
+        if implying.contains(&implied.to_node(None).id) {
+            rules.push((implied_by.to_node(None), via.to_node(None)));
+        }
+    });
+    rules
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Outbound,
+    Inbound,
+}
+
+// outcome of `RBAC::walk`
+struct Walk {
+    // start -> the vertex `visit` stopped at
+    path: Option<Vec<Uuid>>,
+    depth_exceeded: bool,
+    // from -> to of every edge the walk followed, including those to already seen vertices
+    followed: HashMap<Uuid, Vec<Uuid>>,
+}
+
+// carried through a check and the checks of implied roles it starts
+#[derive(Default)]
+struct CheckState {
+    in_progress: HashSet<String>,
+    // first cycle a denied walk ran into
+    cycle: Option<Vec<Uuid>>,
+}
+
+// depth-first search of the edges a walk followed, an edge back to a vertex still on the
+// stack closes a cycle. returns the vertices of the cycle starting and ending at that vertex.
+fn find_cycle(followed: &HashMap<Uuid, Vec<Uuid>>, start: Uuid) -> Option<Vec<Uuid>> {
+    // vertex and the index of its next edge to follow
+    let mut stack = vec![(start, 0)];
+    let mut on_stack = HashSet::from([start]);
+    let mut done = HashSet::new();
+
+    while let Some((v, next)) = stack.last_mut() {
+        let v = *v;
+        let to = followed.get(&v).and_then(|tos| tos.get(*next)).copied();
+        *next += 1;
+
+        match to {
+            Some(to) if on_stack.contains(&to) => {
+                let at = stack.iter().position(|(u, _)| *u == to)?;
+                let mut cycle: Vec<Uuid> = stack[at..].iter().map(|(u, _)| *u).collect();
+                cycle.push(to);
+                return Some(cycle);
+            }
+            Some(to) if !done.contains(&to) => {
+                on_stack.insert(to);
+                stack.push((to, 0));
+            }
+            Some(_) => {}
+            None => {
+                stack.pop();
+                on_stack.remove(&v);
+                done.insert(v);
+            }
+        }
     }
+
+    None
+}
+
+// one RBAC is meant to be shared across threads behind an Arc
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn is_send_sync<T: Send + Sync>() {}
+    is_send_sync::<RBAC>();
+    is_send_sync::<EntityRelationship>();
 }
@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::{FromRequestParts, Path, Request},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{nonblocking::AsyncRBAC, EntityRelationship, RBACError, ToNode};
+
+type SubjectFn = Arc<dyn Fn(&Parts) -> Option<Box<dyn ToNode + Send>> + Send + Sync>;
+type ObjectFn =
+    Arc<dyn Fn(&HashMap<String, String>) -> Option<Box<dyn ToNode + Send>> + Send + Sync>;
+
+// Tower layer guarding routes with `RBAC::allowed`, e.g.
+//
+// Router::new()
+//     .route("/posts/:id", get(show_post))
+//     .route_layer(RequireRoleLayer::new(
+//         rbac,
+//         |parts| parts.extensions.get::<User>().cloned(),
+//         PostRoles::Viewer,
+//         |params| Some(Post { id: params.get("id")?.parse().ok()? }),
+//     ));
+//
+// The subject comes from the request (usually something an authentication layer put into
+// the extensions) and the object from the path parameters. Requests without a subject get
+// 401, requests whose path doesn't name an object get 404, and denied ones 403.
+#[derive(Clone)]
+pub struct RequireRoleLayer {
+    rbac: AsyncRBAC,
+    subject: SubjectFn,
+    role: Arc<dyn ToNode + Send + Sync>,
+    object: ObjectFn,
+}
+
+impl RequireRoleLayer {
+    pub fn new<F, S, R, G, O>(rbac: AsyncRBAC, subject: F, role: R, object: G) -> Self
+    where
+        F: Fn(&Parts) -> Option<S> + Send + Sync + 'static,
+        S: ToNode + Send + 'static,
+        R: ToNode + Send + Sync + 'static,
+        G: Fn(&HashMap<String, String>) -> Option<O> + Send + Sync + 'static,
+        O: ToNode + Send + 'static,
+    {
+        RequireRoleLayer {
+            rbac,
+            subject: Arc::new(move |parts| {
+                subject(parts).map(|s| Box::new(s) as Box<dyn ToNode + Send>)
+            }),
+            role: Arc::new(role),
+            object: Arc::new(move |params| {
+                object(params).map(|o| Box::new(o) as Box<dyn ToNode + Send>)
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequireRoleLayer {
+    type Service = RequireRole<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequireRole {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequireRole<S> {
+    inner: S,
+    layer: RequireRoleLayer,
+}
+
+impl<S> Service<Request> for RequireRole<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // the clone isn't necessarily ready, keep the one that was polled
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+
+            let subject = match (layer.subject)(&parts) {
+                Some(subject) => subject,
+                None => return Ok(StatusCode::UNAUTHORIZED.into_response()),
+            };
+
+            let params =
+                match Path::<HashMap<String, String>>::from_request_parts(&mut parts, &()).await {
+                    Ok(Path(params)) => params,
+                    Err(_) => HashMap::new(),
+                };
+            let object = match (layer.object)(&params) {
+                Some(object) => object,
+                None => return Ok(StatusCode::NOT_FOUND.into_response()),
+            };
+
+            let target = EntityRelationship::new(&*subject, &*layer.role, &*object);
+            match layer.rbac.allowed(target).await {
+                Ok(true) => inner.call(Request::from_parts(parts, body)).await,
+                // never seen subjects or objects have no grants
+                Ok(false) | Err(RBACError::VertexNotFound) => {
+                    Ok(StatusCode::FORBIDDEN.into_response())
+                }
+                Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+            }
+        })
+    }
+}
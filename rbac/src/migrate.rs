@@ -0,0 +1,248 @@
+use std::collections::{HashMap, HashSet};
+
+use indradb::QueryExt;
+use uuid::Uuid;
+
+use crate::{
+    decode_key_part, encode_key_part,
+    schema::{NamespaceDef, Schema},
+    vertex_id, RBACError, ToNode, ENTITY_TO_ROLE, INHERITS, MEMBERSHIP, RBAC, ROLE_TO_ENTITY,
+};
+
+// Brings a store written by an older version up to date. Every migration is idempotent,
+// running them again on a migrated store changes nothing.
+pub struct Migration {
+    pub name: &'static str,
+    run: fn(&RBAC, &Schema) -> Result<usize, RBACError>,
+}
+
+// keys have to be canonical before the others read them
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "canonical_keys",
+        run: canonical_keys,
+    },
+    Migration {
+        name: "backfill_membership",
+        run: backfill_membership,
+    },
+];
+
+// runs every migration in order, returning how many vertices or edges each one changed
+pub fn run(rbac: &RBAC, schema: &Schema) -> Result<Vec<(&'static str, usize)>, RBACError> {
+    MIGRATIONS
+        .iter()
+        .map(|migration| Ok((migration.name, (migration.run)(rbac, schema)?)))
+        .collect()
+}
+
+// Vertices written before keys were escaped had ambiguous keys when ids contained `_`, and
+// random vertex ids. Each one is moved, with its properties and edges, to the vertex of its
+// canonical key, merging with the vertex a newer write may have created there.
+fn canonical_keys(rbac: &RBAC, schema: &Schema) -> Result<usize, RBACError> {
+    let vertices = rbac.get_all_vertices()?;
+    let keys: HashMap<Uuid, String> = vertices.iter().map(|v| (v.id, v.t.to_string())).collect();
+    let edges = rbac.get_all_edges()?;
+
+    // role vertex -> the object it's a role on
+    let objects: HashMap<Uuid, Uuid> = edges
+        .iter()
+        .filter(|e| e.t.as_str() == ROLE_TO_ENTITY)
+        .map(|e| (e.outbound_id, e.inbound_id))
+        .collect();
+
+    // every vertex an edge treats as a role, also those written without a role_to_entity edge
+    let roles: HashSet<Uuid> = edges
+        .iter()
+        .flat_map(|e| match e.t.as_str() {
+            ROLE_TO_ENTITY => vec![e.outbound_id],
+            INHERITS => vec![e.outbound_id, e.inbound_id],
+            ENTITY_TO_ROLE => vec![e.inbound_id],
+            _ => Vec::new(),
+        })
+        .collect();
+
+    let canonical_entity_key = |id: &Uuid, key: &str| {
+        if vertex_id(key) == *id {
+            return Some(key.to_string());
+        }
+        legacy_entity_key(schema, key)
+    };
+
+    // old vertex -> its canonical key
+    let mut moved: HashMap<Uuid, String> = HashMap::new();
+    for v in &vertices {
+        let key = v.t.as_str();
+        if vertex_id(key) == v.id {
+            continue;
+        }
+
+        let canonical = match objects.get(&v.id) {
+            // role vertices were keyed `{object key}_{Role}`
+            Some(object) => keys.get(object).and_then(|object_key| {
+                let role = key.strip_prefix(object_key.as_str())?.strip_prefix('_')?;
+                let object_key = canonical_entity_key(object, object_key)?;
+                Some(format!("{}_{}", object_key, encode_key_part(role)))
+            }),
+            None if roles.contains(&v.id) => legacy_role_key(schema, key),
+            None => legacy_entity_key(schema, key),
+        };
+
+        // vertices outside the schema are left alone
+        if let Some(canonical) = canonical {
+            moved.insert(v.id, canonical);
+        }
+    }
+
+    let membership = indradb::Identifier::new(MEMBERSHIP).unwrap();
+
+    for (old, key) in &moved {
+        let v = indradb::Vertex::with_id(
+            vertex_id(key),
+            indradb::Identifier::new(key.clone()).unwrap(),
+        );
+        rbac.db.create_vertex(&v)?;
+
+        let q = indradb::SpecificVertexQuery::single(*old)
+            .properties()?
+            .name(membership.clone());
+        let marked = indradb::util::extract_vertex_properties(rbac.db.get(q)?)
+            .unwrap_or_default()
+            .iter()
+            .any(|vp| !vp.props.is_empty());
+        if marked {
+            rbac.db.set_properties(
+                indradb::SpecificVertexQuery::single(v.id),
+                membership.clone(),
+                &indradb::Json::new(serde_json::Value::Bool(true)),
+            )?;
+        }
+    }
+
+    let new_id = |id: &Uuid| moved.get(id).map(|key| vertex_id(key)).unwrap_or(*id);
+    for e in &edges {
+        if moved.contains_key(&e.outbound_id) || moved.contains_key(&e.inbound_id) {
+            rbac.db.create_edge(&indradb::Edge::new(
+                new_id(&e.outbound_id),
+                e.t.clone(),
+                new_id(&e.inbound_id),
+            ))?;
+        }
+    }
+
+    // takes the old edges with them
+    if !moved.is_empty() {
+        rbac.db.delete(indradb::SpecificVertexQuery::new(
+            moved.keys().copied().collect(),
+        ))?;
+    }
+
+    Ok(moved.len())
+}
+
+// `Namespace_id` as written before escaping
+fn legacy_entity_key(schema: &Schema, key: &str) -> Option<String> {
+    let (namespace, id) = legacy_namespace(schema, key)?;
+    let entity = schema.entity(&namespace.name, id).ok()?;
+    Some(entity.to_node(None).to_string())
+}
+
+// `Namespace_id_Role` as written before escaping, for role vertices without a role_to_entity
+// edge naming their object. the role has to be one of the namespace's, the longest matching
+// name wins.
+fn legacy_role_key(schema: &Schema, key: &str) -> Option<String> {
+    let (namespace, rest) = legacy_namespace(schema, key)?;
+    let (id, role) = namespace
+        .roles
+        .iter()
+        .filter_map(|role| {
+            let id = rest.strip_suffix(&role.name)?.strip_suffix('_')?;
+            Some((id, role))
+        })
+        .filter(|(id, _)| !id.is_empty())
+        .max_by_key(|(_, role)| role.name.len())?;
+
+    let object = schema.entity(&namespace.name, id).ok()?;
+    Some(format!(
+        "{}_{}",
+        object.to_node(None).to_string(),
+        encode_key_part(&role.name)
+    ))
+}
+
+// splits off the namespace of a key written before escaping, preferring the longest name
+fn legacy_namespace<'a>(schema: &'a Schema, key: &'a str) -> Option<(&'a NamespaceDef, &'a str)> {
+    schema
+        .namespaces
+        .iter()
+        .filter_map(|namespace| {
+            let rest = key.strip_prefix(&namespace.name)?.strip_prefix('_')?;
+            Some((namespace, rest))
+        })
+        .max_by_key(|(namespace, _)| namespace.name.len())
+}
+
+// role vertices created before `allowed` started following `membership` don't carry the marker
+fn backfill_membership(rbac: &RBAC, schema: &Schema) -> Result<usize, RBACError> {
+    let keys: HashMap<Uuid, String> = rbac
+        .get_all_vertices()?
+        .into_iter()
+        .map(|v| (v.id, v.t.to_string()))
+        .collect();
+
+    let membership = indradb::Identifier::new(MEMBERSHIP).unwrap();
+    let mut changed = 0;
+
+    for edge in rbac.get_all_edges()? {
+        if edge.t.as_str() != ROLE_TO_ENTITY {
+            continue;
+        }
+
+        let (role_key, object_key) = match (keys.get(&edge.outbound_id), keys.get(&edge.inbound_id))
+        {
+            (Some(role_key), Some(object_key)) => (role_key, object_key),
+            _ => continue,
+        };
+
+        // role vertices are keyed `{object key}_{Role}`
+        let role = match role_key
+            .strip_prefix(object_key.as_str())
+            .and_then(|role| role.strip_prefix('_'))
+            .and_then(decode_key_part)
+        {
+            Some(role) => role,
+            None => continue,
+        };
+
+        let is_membership = schema
+            .entity_from_key(object_key)
+            .and_then(|object| {
+                let namespace = schema.namespace(object.namespace()).ok()?;
+                namespace.role(&role).map(|role| role.membership)
+            })
+            .unwrap_or(false);
+        if !is_membership {
+            continue;
+        }
+
+        let q = indradb::SpecificVertexQuery::single(edge.outbound_id)
+            .properties()?
+            .name(membership.clone());
+        let marked = indradb::util::extract_vertex_properties(rbac.db.get(q)?)
+            .unwrap_or_default()
+            .iter()
+            .any(|vp| !vp.props.is_empty());
+        if marked {
+            continue;
+        }
+
+        rbac.db.set_properties(
+            indradb::SpecificVertexQuery::single(edge.outbound_id),
+            membership.clone(),
+            &indradb::Json::new(serde_json::Value::Bool(true)),
+        )?;
+        changed += 1;
+    }
+
+    Ok(changed)
+}
@@ -0,0 +1,173 @@
+use std::{
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    thread,
+};
+
+use tokio::sync::oneshot;
+
+use crate::{
+    Diagnosis, EntityRelationship, Precondition, RBACError, ToNode, Update, WriteMode, RBAC,
+};
+
+// Async facade over `RBAC` for tokio services.
+// Every call runs on a pool of threads of its own, so RocksDB I/O never stalls the async
+// workers and long traversals don't take up tokio's blocking pool, which the rest of the
+// application uses for file I/O and the like. Calls wait in a queue while all threads are busy.
+#[derive(Clone)]
+pub struct AsyncRBAC {
+    inner: Arc<RBAC>,
+    pool: Arc<Pool>,
+}
+
+impl AsyncRBAC {
+    // one thread per cpu
+    pub fn new(rbac: RBAC) -> Self {
+        Self::from_arc(Arc::new(rbac))
+    }
+
+    pub fn from_arc(inner: Arc<RBAC>) -> Self {
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self::with_threads(inner, threads)
+    }
+
+    // runs calls on `threads` threads, at least one
+    pub fn with_threads(inner: Arc<RBAC>, threads: usize) -> Self {
+        AsyncRBAC {
+            inner,
+            pool: Arc::new(Pool::new(threads.max(1))),
+        }
+    }
+
+    pub fn inner(&self) -> &Arc<RBAC> {
+        &self.inner
+    }
+
+    pub async fn add_relationship(
+        &self,
+        relationship: EntityRelationship,
+    ) -> Result<bool, RBACError> {
+        self.run(move |rbac| rbac.add_relationship(&relationship))
+            .await
+    }
+
+    pub async fn write_relationship(
+        &self,
+        relationship: EntityRelationship,
+        mode: WriteMode,
+        preconditions: Vec<Precondition>,
+    ) -> Result<bool, RBACError> {
+        self.run(move |rbac| rbac.write_relationship(&relationship, mode, &preconditions))
+            .await
+    }
+
+    pub async fn write_batch(&self, updates: Vec<Update>) -> Result<Vec<bool>, RBACError> {
+        self.run(move |rbac| rbac.write_batch(&updates)).await
+    }
+
+    pub async fn remove_relationship(
+        &self,
+        relationship: EntityRelationship,
+    ) -> Result<bool, RBACError> {
+        self.run(move |rbac| rbac.remove_relationship(&relationship))
+            .await
+    }
+
+    pub async fn allowed(&self, target: EntityRelationship) -> Result<bool, RBACError> {
+        self.run(move |rbac| rbac.allowed(&target)).await
+    }
+
+    pub async fn explain(
+        &self,
+        target: EntityRelationship,
+    ) -> Result<Option<Vec<String>>, RBACError> {
+        self.run(move |rbac| rbac.explain(&target)).await
+    }
+
+    pub async fn diagnose(&self, target: EntityRelationship) -> Result<Diagnosis, RBACError> {
+        self.run(move |rbac| rbac.diagnose(&target)).await
+    }
+
+    pub async fn lookup_objects<S, R>(&self, subject: S, role: R) -> Result<Vec<String>, RBACError>
+    where
+        S: ToNode + Send + 'static,
+        R: ToNode + Send + 'static,
+    {
+        self.run(move |rbac| rbac.lookup_objects(&subject, &role))
+            .await
+    }
+
+    pub async fn lookup_subjects<R, O>(&self, role: R, object: O) -> Result<Vec<String>, RBACError>
+    where
+        R: ToNode + Send + 'static,
+        O: ToNode + Send + 'static,
+    {
+        self.run(move |rbac| rbac.lookup_subjects(&role, &object))
+            .await
+    }
+
+    async fn run<T, F>(&self, f: F) -> Result<T, RBACError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&RBAC) -> Result<T, RBACError> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let (tx, rx) = oneshot::channel();
+        self.pool.execute(Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(&inner)));
+            // the caller may have stopped waiting
+            let _ = tx.send(result);
+        }))?;
+
+        match rx.await {
+            Ok(Ok(result)) => result,
+            // surface a panic of the call here, as if it ran on this task
+            Ok(Err(panic)) => panic::resume_unwind(panic),
+            Err(_) => Err(RBACError::Cancelled),
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Threads taking jobs off a shared queue. They stop once the last `AsyncRBAC` sharing the
+// pool is dropped and the queue runs empty.
+struct Pool {
+    jobs: Mutex<mpsc::Sender<Job>>,
+}
+
+impl Pool {
+    fn new(threads: usize) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+
+        for i in 0..threads {
+            let queue = queue.clone();
+            thread::Builder::new()
+                .name(format!("rbac-{}", i))
+                .spawn(move || loop {
+                    // the lock is only held while waiting for the next job
+                    let job = queue.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })
+                .expect("failed to spawn an rbac pool thread");
+        }
+
+        Pool {
+            jobs: Mutex::new(jobs),
+        }
+    }
+
+    // fails when every thread is gone, which only a panic outside a job could cause
+    fn execute(&self, job: Job) -> Result<(), RBACError> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(job)
+            .map_err(|_| RBACError::Cancelled)
+    }
+}
@@ -0,0 +1,424 @@
+use std::{fs, path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    decode_key_part, encode_key_part, NamespaceRole, NamespaceToString, NamespaceToStringAndRole,
+    Node, RoleHierarchy, ToNode,
+};
+
+// Policy model loaded at runtime, for tools that can't use the derive macros (servers, cli).
+// It produces the same nodes as `#[derive(Namespace, Role, ToNode)]` would, e.g.
+//
+// {
+//   "namespaces": [
+//     { "name": "User" },
+//     { "name": "Group", "roles": [
+//       { "name": "Admin" },
+//       { "name": "Member", "child_of": "Admin", "membership": true }
+//     ] },
+//     { "name": "Post", "roles": [
+//       { "name": "Writer", "implied_by": [
+//         { "namespace": "Group", "role": "Admin", "via": "OwnedBy" }
+//       ] },
+//       { "name": "OwnedBy" }
+//     ] }
+//   ]
+// }
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Schema {
+    pub namespaces: Vec<NamespaceDef>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NamespaceDef {
+    pub name: String,
+    #[serde(default)]
+    pub roles: Vec<RoleDef>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoleDef {
+    pub name: String,
+    // parents, either `"child_of": "Writer"` or `"child_of": ["Moderator", "Writer"]`
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub child_of: Vec<String>,
+    #[serde(default)]
+    pub membership: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub implied_by: Vec<ImpliedByDef>,
+}
+
+// holders of `role` of `namespace` on an entity holding `via` on an object also hold the role
+// it's declared on, the runtime counterpart of `#[implied_by(...)]`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImpliedByDef {
+    pub namespace: String,
+    pub role: String,
+    pub via: String,
+}
+
+fn one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(parent) => vec![parent],
+        OneOrMany::Many(parents) => parents,
+    })
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    DuplicateNamespace(String),
+    UnknownNamespace(String),
+    // namespace, role
+    UnknownRole(String, String),
+    InvalidTuple(String),
+}
+
+impl From<std::io::Error> for SchemaError {
+    fn from(e: std::io::Error) -> Self {
+        SchemaError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SchemaError {
+    fn from(e: serde_json::Error) -> Self {
+        SchemaError::Json(e)
+    }
+}
+
+impl Schema {
+    pub fn from_json(s: &str) -> Result<Self, SchemaError> {
+        let schema: Schema = serde_json::from_str(s)?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SchemaError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn validate(&self) -> Result<(), SchemaError> {
+        for (i, namespace) in self.namespaces.iter().enumerate() {
+            if self.namespaces[..i]
+                .iter()
+                .any(|other| other.name == namespace.name)
+            {
+                return Err(SchemaError::DuplicateNamespace(namespace.name.clone()));
+            }
+
+            for role in &namespace.roles {
+                for parent in &role.child_of {
+                    if namespace.role(parent).is_none() {
+                        return Err(SchemaError::UnknownRole(
+                            namespace.name.clone(),
+                            parent.clone(),
+                        ));
+                    }
+                }
+
+                for implied in &role.implied_by {
+                    if namespace.role(&implied.via).is_none() {
+                        return Err(SchemaError::UnknownRole(
+                            namespace.name.clone(),
+                            implied.via.clone(),
+                        ));
+                    }
+                    if self
+                        .namespace(&implied.namespace)?
+                        .role(&implied.role)
+                        .is_none()
+                    {
+                        return Err(SchemaError::UnknownRole(
+                            implied.namespace.clone(),
+                            implied.role.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn namespace(&self, name: &str) -> Result<&NamespaceDef, SchemaError> {
+        self.namespaces
+            .iter()
+            .find(|namespace| namespace.name == name)
+            .ok_or_else(|| SchemaError::UnknownNamespace(name.to_string()))
+    }
+
+    // entities and roles keep a copy of the whole schema, roles implied across namespaces
+    // need to reach the other ones
+    fn namespace_ref(&self, name: &str) -> Result<NamespaceRef, SchemaError> {
+        let index = self
+            .namespaces
+            .iter()
+            .position(|namespace| namespace.name == name)
+            .ok_or_else(|| SchemaError::UnknownNamespace(name.to_string()))?;
+
+        Ok(NamespaceRef {
+            schema: Arc::new(self.clone()),
+            index,
+        })
+    }
+
+    pub fn entity(&self, namespace: &str, id: &str) -> Result<SchemaEntity, SchemaError> {
+        Ok(SchemaEntity {
+            namespace: self.namespace_ref(namespace)?,
+            id: id.to_string(),
+        })
+    }
+
+    // roles always belong to the namespace of the object they're granted on
+    pub fn role(&self, namespace: &str, role: &str) -> Result<SchemaRole, SchemaError> {
+        let namespace = self.namespace_ref(namespace)?;
+        if namespace.def().role(role).is_none() {
+            return Err(SchemaError::UnknownRole(
+                namespace.def().name.clone(),
+                role.to_string(),
+            ));
+        }
+
+        Ok(SchemaRole {
+            namespace,
+            name: role.to_string(),
+        })
+    }
+
+    // `Namespace:id`
+    pub fn parse_entity(&self, s: &str) -> Result<SchemaEntity, SchemaError> {
+        let (namespace, id) = s
+            .split_once(':')
+            .filter(|(namespace, id)| !namespace.is_empty() && !id.is_empty())
+            .ok_or_else(|| SchemaError::InvalidTuple(s.to_string()))?;
+
+        self.entity(namespace, id)
+    }
+
+    // `Namespace:id#role`, the role is looked up on the object's namespace
+    pub fn parse_object_role(&self, s: &str) -> Result<(SchemaRole, SchemaEntity), SchemaError> {
+        let (object, role) = s
+            .split_once('#')
+            .ok_or_else(|| SchemaError::InvalidTuple(s.to_string()))?;
+
+        let object = self.parse_entity(object)?;
+        let role = self.role(object.namespace(), role)?;
+        Ok((role, object))
+    }
+
+    // `Namespace:id#role@Namespace:id`, i.e. `object#role@subject`
+    pub fn parse_relationship(
+        &self,
+        s: &str,
+    ) -> Result<(SchemaEntity, SchemaRole, SchemaEntity), SchemaError> {
+        let (object_role, subject) = s
+            .split_once('@')
+            .ok_or_else(|| SchemaError::InvalidTuple(s.to_string()))?;
+
+        let (role, object) = self.parse_object_role(object_role)?;
+        let subject = self.parse_entity(subject)?;
+        Ok((subject, role, object))
+    }
+
+    // stored entity key (`Namespace_id`) back to an entity
+    pub fn entity_from_key(&self, key: &str) -> Option<SchemaEntity> {
+        let (namespace, id) = key.split_once('_')?;
+
+        Some(SchemaEntity {
+            namespace: self.namespace_ref(&decode_key_part(namespace)?).ok()?,
+            id: decode_key_part(id)?,
+        })
+    }
+}
+
+impl NamespaceDef {
+    pub fn role(&self, name: &str) -> Option<&RoleDef> {
+        self.roles.iter().find(|role| role.name == name)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct NamespaceRef {
+    schema: Arc<Schema>,
+    index: usize,
+}
+
+impl NamespaceRef {
+    fn def(&self) -> &NamespaceDef {
+        &self.schema.namespaces[self.index]
+    }
+
+    // another namespace of the same schema
+    fn other(&self, name: &str) -> Option<NamespaceRef> {
+        let index = self
+            .schema
+            .namespaces
+            .iter()
+            .position(|namespace| namespace.name == name)?;
+
+        Some(NamespaceRef {
+            schema: self.schema.clone(),
+            index,
+        })
+    }
+}
+
+// entity of a schema namespace, the runtime counterpart of `#[derive(ToNode)]`
+#[derive(Clone, Debug)]
+pub struct SchemaEntity {
+    namespace: NamespaceRef,
+    id: String,
+}
+
+impl SchemaEntity {
+    pub fn namespace(&self) -> &str {
+        &self.namespace.def().name
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+// `Namespace:id`, the inverse of `Schema::parse_entity`
+impl std::fmt::Display for SchemaEntity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.namespace.def().name, self.id)
+    }
+}
+
+impl ToNode for SchemaEntity {
+    fn to_node(&self, parent_id: Option<String>) -> Node {
+        Node::new(
+            Box::new(SchemaNamespace {
+                namespace: self.namespace.clone(),
+                id: parent_id,
+            }),
+            self.id.clone(),
+        )
+    }
+}
+
+// role of a schema namespace, the runtime counterpart of `#[derive(Role)]`
+#[derive(Clone, Debug)]
+pub struct SchemaRole {
+    namespace: NamespaceRef,
+    name: String,
+}
+
+impl SchemaRole {
+    pub fn namespace(&self) -> &str {
+        &self.namespace.def().name
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sibling(&self, name: &str) -> Box<dyn RoleHierarchy> {
+        Box::new(SchemaRole {
+            namespace: self.namespace.clone(),
+            name: name.to_string(),
+        })
+    }
+}
+
+impl ToNode for SchemaRole {
+    fn to_node(&self, group_id: Option<String>) -> Node {
+        Node::new(
+            Box::new(SchemaNamespace {
+                namespace: self.namespace.clone(),
+                id: group_id,
+            }),
+            self.name.clone(),
+        )
+    }
+}
+
+impl RoleHierarchy for SchemaRole {
+    fn iter_hierarchy(&self, f: &mut dyn FnMut(Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>)) {
+        for role in &self.namespace.def().roles {
+            for parent in &role.child_of {
+                f(self.sibling(parent), self.sibling(&role.name));
+            }
+        }
+    }
+
+    fn iter_all(&self, f: &mut dyn FnMut(Box<dyn RoleHierarchy>)) {
+        for role in &self.namespace.def().roles {
+            f(self.sibling(&role.name));
+        }
+    }
+
+    fn is_membership(&self) -> bool {
+        self.namespace
+            .def()
+            .role(&self.name)
+            .map(|role| role.membership)
+            .unwrap_or(false)
+    }
+
+    fn iter_implied(
+        &self,
+        f: &mut dyn FnMut(Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>, Box<dyn RoleHierarchy>),
+    ) {
+        for role in &self.namespace.def().roles {
+            for implied in &role.implied_by {
+                // checked by `Schema::validate`
+                if let Some(namespace) = self.namespace.other(&implied.namespace) {
+                    f(
+                        self.sibling(&role.name),
+                        Box::new(SchemaRole {
+                            namespace,
+                            name: implied.role.clone(),
+                        }),
+                        self.sibling(&implied.via),
+                    );
+                }
+            }
+        }
+    }
+}
+
+struct SchemaNamespace {
+    namespace: NamespaceRef,
+    id: Option<String>,
+}
+
+impl NamespaceToString for SchemaNamespace {
+    fn to_string(&self) -> String {
+        match &self.id {
+            Some(id) => format!(
+                "{}_{}",
+                encode_key_part(&self.namespace.def().name),
+                encode_key_part(id)
+            ),
+            None => encode_key_part(&self.namespace.def().name),
+        }
+    }
+}
+
+impl NamespaceRole for SchemaNamespace {
+    fn get_roles(&self) -> Option<Box<dyn RoleHierarchy>> {
+        let role = self.namespace.def().roles.first()?;
+        Some(Box::new(SchemaRole {
+            namespace: self.namespace.clone(),
+            name: role.name.clone(),
+        }))
+    }
+}
+
+impl NamespaceToStringAndRole for SchemaNamespace {}